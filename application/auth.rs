@@ -1,7 +1,20 @@
+pub mod asymmetric;
+mod client;
+mod crate_pattern;
+pub mod jwt;
+mod login;
 mod middleware;
+pub mod oidc;
+pub mod paseto;
+mod scope;
+pub mod session;
 mod token;
 mod user;
 
-pub use middleware::token_authenticator;
+pub use crate_pattern::CratePattern;
+pub use login::{login, login_callback, LoginState};
+pub use middleware::{read_authenticator, token_authenticator};
+pub use paseto::PublicKey;
+pub use scope::{EndpointScope, TokenScopes};
 pub use token::{generate_new_token, hash};
 pub use user::AuthenticatedUser;
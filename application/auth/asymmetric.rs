@@ -0,0 +1,265 @@
+//! Checking the claims of Cargo's asymmetric tokens, as described in
+//! https://rust-lang.github.io/rfcs/3231-cargo-asymmetric-tokens.html
+//!
+//! Instead of sending a secret, Cargo signs a short-lived PASETO for every request with
+//! a key the user registered. The footer names the key and the registry the token is
+//! meant for, and the message says which change, if any, the token may be used for.
+use anyhow::{anyhow, bail};
+use axum::http::Method;
+use chrono::{DateTime, Duration, Utc};
+use semver::Version;
+use serde::Deserialize;
+
+use crate::error::{AppError, AppResult};
+
+/// How long after Cargo signs a token it's accepted.
+pub const MAX_TOKEN_AGE: Duration = Duration::minutes(5);
+/// How far ahead of the registry's clock a token may be signed.
+const MAX_CLOCK_SKEW: Duration = Duration::minutes(1);
+
+#[derive(Debug, Deserialize)]
+pub struct Footer {
+    /// The index URL of the registry the token was signed for.
+    pub url: String,
+    /// The PASERK id of the key that signed the token.
+    pub kip: String,
+}
+
+impl Footer {
+    pub fn parse(footer: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_slice(footer).map_err(|_| anyhow!("the token footer is invalid"))
+    }
+
+    /// Checks that the token was signed for this registry.
+    ///
+    /// Cargo includes the `sparse+` prefix of the index URL, which is ignored
+    /// along with trailing slashes.
+    pub fn check_url(&self, index_url: &str) -> anyhow::Result<()> {
+        if normalize_url(&self.url) != normalize_url(index_url) {
+            bail!("the token was signed for the registry at {}", self.url);
+        }
+
+        Ok(())
+    }
+}
+
+fn normalize_url(url: &str) -> &str {
+    url.strip_prefix("sparse+")
+        .unwrap_or(url)
+        .trim_end_matches('/')
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Mutation {
+    Publish,
+    Yank,
+    Unyank,
+    Owners,
+}
+
+impl Mutation {
+    /// The mutations a token may be signed for to be used for the request,
+    /// where `None` stands for a token that's only good for reading.
+    ///
+    /// Listing the owners changes nothing, but may be signed like changing them.
+    pub fn allowed_for_request(method: &Method, path: &str) -> &'static [Option<Self>] {
+        let endpoint = path.trim_end_matches('/').rsplit('/').next();
+        match (method, endpoint) {
+            (&Method::PUT, Some("new")) => &[Some(Mutation::Publish)],
+            (&Method::DELETE, Some("yank")) => &[Some(Mutation::Yank)],
+            (&Method::PUT, Some("unyank")) => &[Some(Mutation::Unyank)],
+            (&Method::GET, Some("owners")) => &[None, Some(Mutation::Owners)],
+            (_, Some("owners")) => &[Some(Mutation::Owners)],
+            _ => &[None],
+        }
+    }
+}
+
+/// The message Cargo signs.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Claims {
+    /// When the token was signed.
+    pub iat: DateTime<Utc>,
+    /// The change the token was signed for, or none for reading from the registry.
+    pub mutation: Option<Mutation>,
+    pub name: Option<String>,
+    pub vers: Option<String>,
+    /// The checksum of the crate being published.
+    pub cksum: Option<String>,
+}
+
+impl Claims {
+    pub fn parse(message: &[u8]) -> anyhow::Result<Self> {
+        serde_json::from_slice(message).map_err(|_| anyhow!("the token message is invalid"))
+    }
+
+    /// Checks that the token is fresh, and was signed for this request.
+    ///
+    /// `crate_name` and `version` are the ones in the path of the request, the crate
+    /// being published is only known once the body is read, see [`Claims::check_publish`].
+    pub fn check_request(
+        &self,
+        allowed_mutations: &[Option<Mutation>],
+        crate_name: Option<&str>,
+        version: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        if now - self.iat > MAX_TOKEN_AGE || self.iat - now > MAX_CLOCK_SKEW {
+            bail!("the token was signed at {}", self.iat);
+        }
+        if !allowed_mutations.contains(&self.mutation) {
+            bail!("the token was signed for {:?}", self.mutation);
+        }
+        if self.mutation.is_some() {
+            check_claim("name", self.name.as_deref(), crate_name)?;
+            check_claim("vers", self.vers.as_deref(), version)?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the token was signed for publishing this exact crate.
+    pub fn check_publish(&self, crate_name: &str, version: &Version, cksum: &str) -> AppResult<()> {
+        let signed_version = self.vers.as_deref().and_then(|v| Version::parse(v).ok());
+        if self.mutation != Some(Mutation::Publish)
+            || self.name.as_deref() != Some(crate_name)
+            || signed_version.as_ref() != Some(version)
+            || self.cksum.as_deref() != Some(cksum)
+        {
+            let detail = format!(
+                "this token was not signed for publishing {} {}",
+                crate_name, version
+            );
+            return Err(AppError::Forbidden(detail));
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks a claim against the request, when the request has a value for it.
+fn check_claim(claim: &str, signed: Option<&str>, requested: Option<&str>) -> anyhow::Result<()> {
+    match requested {
+        Some(requested) if signed != Some(requested) => {
+            bail!("the token was signed for {} {:?}", claim, signed)
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_claims(mutation: Option<Mutation>) -> Claims {
+        Claims {
+            iat: Utc::now(),
+            mutation,
+            name: Some("raktar".to_string()),
+            vers: Some("0.1.0".to_string()),
+            cksum: Some("abc".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_mutations_for_request() {
+        let cases: [(Method, &str, &[Option<Mutation>]); 6] = [
+            (
+                Method::PUT,
+                "/api/v1/crates/new",
+                &[Some(Mutation::Publish)],
+            ),
+            (
+                Method::DELETE,
+                "/api/v1/crates/raktar/0.1.0/yank",
+                &[Some(Mutation::Yank)],
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/raktar/0.1.0/unyank",
+                &[Some(Mutation::Unyank)],
+            ),
+            (
+                Method::PUT,
+                "/api/v1/crates/raktar/owners",
+                &[Some(Mutation::Owners)],
+            ),
+            (
+                Method::GET,
+                "/api/v1/crates/raktar/owners",
+                &[None, Some(Mutation::Owners)],
+            ),
+            (Method::GET, "/ra/kt/raktar", &[None]),
+        ];
+
+        for (method, path, expected) in cases {
+            let actual = Mutation::allowed_for_request(&method, path);
+            assert_eq!(actual, expected, "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_registry_url() {
+        let footer = Footer {
+            url: "sparse+https://raktar.io/".to_string(),
+            kip: "k3.pid.id".to_string(),
+        };
+
+        assert!(footer.check_url("sparse+https://raktar.io/").is_ok());
+        assert!(footer.check_url("https://raktar.io").is_ok());
+        assert!(footer.check_url("sparse+https://other.io/").is_err());
+    }
+
+    #[test]
+    fn test_old_and_future_tokens() {
+        let claims = build_claims(None);
+
+        assert!(claims
+            .check_request(&[None], None, None, Utc::now())
+            .is_ok());
+        let later = Utc::now() + MAX_TOKEN_AGE + Duration::seconds(1);
+        assert!(claims.check_request(&[None], None, None, later).is_err());
+        let earlier = Utc::now() - Duration::minutes(2);
+        assert!(claims.check_request(&[None], None, None, earlier).is_err());
+    }
+
+    #[test]
+    fn test_request_claims() {
+        let now = Utc::now();
+        let claims = build_claims(Some(Mutation::Yank));
+
+        let yank = &[Some(Mutation::Yank)];
+        assert!(claims
+            .check_request(yank, Some("raktar"), Some("0.1.0"), now)
+            .is_ok());
+        assert!(claims
+            .check_request(yank, Some("other"), Some("0.1.0"), now)
+            .is_err());
+        assert!(claims
+            .check_request(yank, Some("raktar"), Some("0.2.0"), now)
+            .is_err());
+        assert!(claims
+            .check_request(
+                &[Some(Mutation::Unyank)],
+                Some("raktar"),
+                Some("0.1.0"),
+                now
+            )
+            .is_err());
+        assert!(claims.check_request(&[None], None, None, now).is_err());
+    }
+
+    #[test]
+    fn test_publish_claims() {
+        let claims = build_claims(Some(Mutation::Publish));
+        let version = Version::new(0, 1, 0);
+
+        assert!(claims.check_publish("raktar", &version, "abc").is_ok());
+        assert!(claims.check_publish("other", &version, "abc").is_err());
+        assert!(claims
+            .check_publish("raktar", &Version::new(0, 2, 0), "abc")
+            .is_err());
+        assert!(claims.check_publish("raktar", &version, "def").is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Requests to identity providers, which are only ever made over HTTPS.
+use anyhow::bail;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::client::HttpConnector;
+use hyper::{Body, Client, Method, Request, Response};
+use hyper_rustls::HttpsConnector;
+
+fn build_client() -> Client<HttpsConnector<HttpConnector>> {
+    let connector = hyper_rustls::HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_only()
+        .enable_http1()
+        .build();
+
+    Client::builder().build(connector)
+}
+
+pub async fn get(url: &str) -> anyhow::Result<Vec<u8>> {
+    let response = build_client().get(url.parse()?).await?;
+
+    read_body(response).await
+}
+
+/// Posts a form, authenticating with HTTP basic auth when there are credentials.
+pub async fn post_form(
+    url: &str,
+    form: &[(&str, &str)],
+    credentials: Option<(&str, &str)>,
+) -> anyhow::Result<Vec<u8>> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form)
+        .finish();
+    let mut builder = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept", "application/json");
+    if let Some((username, password)) = credentials {
+        // the credentials are form encoded before being put together, see RFC 6749 2.3.1
+        let encode = |value: &str| {
+            url::form_urlencoded::byte_serialize(value.as_bytes()).collect::<String>()
+        };
+        let credentials = format!("{}:{}", encode(username), encode(password));
+        builder = builder.header(
+            "Authorization",
+            format!("Basic {}", STANDARD.encode(credentials)),
+        );
+    }
+
+    let response = build_client()
+        .request(builder.body(Body::from(body))?)
+        .await?;
+
+    read_body(response).await
+}
+
+async fn read_body(response: Response<Body>) -> anyhow::Result<Vec<u8>> {
+    let status = response.status();
+    let body = hyper::body::to_bytes(response.into_body()).await?;
+    if !status.is_success() {
+        bail!(
+            "the request failed with status {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        );
+    }
+
+    Ok(body.to_vec())
+}
@@ -0,0 +1,99 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::crate_name::canonical_crate_name;
+
+/// A pattern that matches crate names, in the style crates.io uses for token scopes.
+///
+/// A pattern is either a full crate name, or a prefix followed by a `*` wildcard,
+/// for example `raktar-*`. A `*` on its own matches every crate. Names are compared
+/// after `-`/`_` and case normalization.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CratePattern(String);
+
+impl CratePattern {
+    pub fn matches(&self, crate_name: &str) -> bool {
+        let name = canonical_crate_name(crate_name);
+        match self.0.strip_suffix('*') {
+            Some(prefix) => name.starts_with(&canonical_crate_name(prefix)),
+            None => name == canonical_crate_name(&self.0),
+        }
+    }
+}
+
+impl FromStr for CratePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        let prefix = pattern.strip_suffix('*').unwrap_or(pattern);
+        if pattern.is_empty() {
+            bail!("crate pattern cannot be empty");
+        }
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("invalid crate pattern {}", pattern);
+        }
+
+        Ok(Self(pattern.to_string()))
+    }
+}
+
+impl TryFrom<String> for CratePattern {
+    type Error = anyhow::Error;
+
+    fn try_from(pattern: String) -> Result<Self, Self::Error> {
+        Self::from_str(&pattern)
+    }
+}
+
+impl From<CratePattern> for String {
+    fn from(pattern: CratePattern) -> Self {
+        pattern.0
+    }
+}
+
+impl fmt::Display for CratePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern(s: &str) -> CratePattern {
+        CratePattern::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_exact_pattern() {
+        assert!(pattern("foo-bar").matches("foo-bar"));
+        assert!(pattern("foo-bar").matches("Foo_Bar"));
+        assert!(!pattern("foo-bar").matches("foo-bar-baz"));
+    }
+
+    #[test]
+    fn test_wildcard_pattern() {
+        assert!(pattern("foo-*").matches("foo-bar"));
+        assert!(pattern("foo-*").matches("foo_baz"));
+        assert!(!pattern("foo-*").matches("foobar"));
+        assert!(pattern("*").matches("anything"));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for p in ["", "foo*bar", "**", "foo.*", "foo bar"] {
+            assert!(
+                CratePattern::from_str(p).is_err(),
+                "{} should be invalid",
+                p
+            );
+        }
+    }
+}
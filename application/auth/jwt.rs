@@ -0,0 +1,270 @@
+//! Verifying the JWTs the web frontend gets from Cognito.
+//!
+//! The keys of the user pool are published as a JSON Web Key Set, which is cached and
+//! loaded again once it's old, or when a token is signed with a key that isn't known yet.
+use anyhow::anyhow;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use tracing::{error, info};
+
+use crate::auth::client;
+
+/// How long the keys are used before they're loaded again.
+const KEYS_MAX_AGE: Duration = Duration::from_secs(60 * 60);
+/// How long to wait between loading the keys for tokens signed with unknown keys,
+/// so that made up key ids can't be used to flood the JWKS endpoint.
+const MIN_RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JwtConfig {
+    /// The `iss` claim tokens must have, for Cognito the URL of the user pool.
+    pub issuer: String,
+    /// The `aud` claim tokens must have, for Cognito the id of the app client.
+    pub audience: String,
+    pub jwks: JwksSource,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JwksSource {
+    Url(String),
+    /// A file with the key set, for testing and running locally.
+    File(PathBuf),
+}
+
+impl JwksSource {
+    /// Where Cognito publishes the keys of the user pool with the given issuer URL.
+    pub fn for_issuer(issuer: &str) -> Self {
+        JwksSource::Url(format!(
+            "{}/.well-known/jwks.json",
+            issuer.trim_end_matches('/')
+        ))
+    }
+
+    async fn load(&self) -> anyhow::Result<JwkSet> {
+        let bytes = match self {
+            JwksSource::File(path) => tokio::fs::read(path).await?,
+            JwksSource::Url(url) => client::get(url).await?,
+        };
+
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+}
+
+#[derive(Clone)]
+pub struct JwtVerifier {
+    config: Arc<JwtConfig>,
+    keys: Arc<RwLock<CachedKeys>>,
+}
+
+#[derive(Default)]
+struct CachedKeys {
+    /// Keys keyed by their id.
+    keys: HashMap<String, DecodingKey>,
+    loaded_at: Option<Instant>,
+}
+
+impl CachedKeys {
+    fn get(&self, key_id: &str, now: Instant) -> Option<DecodingKey> {
+        let loaded_at = self.loaded_at?;
+        if now.duration_since(loaded_at) > KEYS_MAX_AGE {
+            return None;
+        }
+
+        self.keys.get(key_id).cloned()
+    }
+
+    fn can_reload(&self, now: Instant) -> bool {
+        self.loaded_at
+            .is_none_or(|loaded_at| now.duration_since(loaded_at) > MIN_RELOAD_INTERVAL)
+    }
+}
+
+impl JwtVerifier {
+    pub fn new(config: JwtConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            keys: Arc::new(RwLock::new(CachedKeys::default())),
+        }
+    }
+
+    /// Checks the signature, expiry, issuer and audience of the token, and gives back its claims.
+    pub async fn verify<T: DeserializeOwned>(&self, token: &str) -> anyhow::Result<T> {
+        let header = decode_header(token)?;
+        let key_id = header
+            .kid
+            .ok_or_else(|| anyhow!("the token has no key id"))?;
+        let key = self.get_key(&key_id).await?;
+
+        // Cognito only signs with RS256, and the algorithm in the header can't be trusted
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&self.config.issuer]);
+        validation.set_audience(&[&self.config.audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let data = decode::<T>(token, &key, &validation)?;
+
+        Ok(data.claims)
+    }
+
+    async fn get_key(&self, key_id: &str) -> anyhow::Result<DecodingKey> {
+        let now = Instant::now();
+        if let Some(key) = self.keys.read().await.get(key_id, now) {
+            return Ok(key);
+        }
+
+        let mut cached = self.keys.write().await;
+        // the keys may have been loaded while waiting for the lock
+        if let Some(key) = cached.get(key_id, now) {
+            return Ok(key);
+        }
+        if cached.can_reload(now) {
+            self.reload(&mut cached, now).await;
+        }
+
+        // stale keys are still better than none when the keys couldn't be loaded again
+        cached
+            .keys
+            .get(key_id)
+            .cloned()
+            .ok_or_else(|| anyhow!("the token is signed with unknown key {}", key_id))
+    }
+
+    async fn reload(&self, cached: &mut CachedKeys, now: Instant) {
+        cached.loaded_at = Some(now);
+        match self.config.jwks.load().await {
+            Ok(jwks) => {
+                cached.keys = jwks
+                    .keys
+                    .iter()
+                    .filter_map(|jwk| {
+                        let key_id = jwk.common.key_id.clone()?;
+                        let key = DecodingKey::from_jwk(jwk).ok()?;
+                        Some((key_id, key))
+                    })
+                    .collect();
+                info!(keys = cached.keys.len(), "loaded JWT signing keys");
+            }
+            Err(err) => {
+                error!(
+                    error_message = err.to_string(),
+                    "failed to load JWT signing keys"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde::{Deserialize, Serialize};
+
+    const ISSUER: &str = "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_test";
+    const AUDIENCE: &str = "test-client";
+    const PRIVATE_KEY: &[u8] = include_bytes!("../../tests/fixtures/jwt/private_key.pem");
+    const UNKNOWN_PRIVATE_KEY: &[u8] =
+        include_bytes!("../../tests/fixtures/jwt/unknown_private_key.pem");
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Claims {
+        iss: String,
+        aud: String,
+        exp: u64,
+        autogen_id: String,
+    }
+
+    impl Claims {
+        fn valid() -> Self {
+            Self {
+                iss: ISSUER.to_string(),
+                aud: AUDIENCE.to_string(),
+                exp: jsonwebtoken::get_current_timestamp() + 300,
+                autogen_id: "1".to_string(),
+            }
+        }
+    }
+
+    fn build_verifier() -> JwtVerifier {
+        let jwks = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt/jwks.json");
+        JwtVerifier::new(JwtConfig {
+            issuer: ISSUER.to_string(),
+            audience: AUDIENCE.to_string(),
+            jwks: JwksSource::File(jwks.into()),
+        })
+    }
+
+    fn sign(claims: &Claims, private_key: &[u8]) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("test-key-1".to_string());
+        let key = EncodingKey::from_rsa_pem(private_key).unwrap();
+
+        encode(&header, claims, &key).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_token() {
+        let verifier = build_verifier();
+        let claims = Claims::valid();
+
+        let verified: Claims = verifier.verify(&sign(&claims, PRIVATE_KEY)).await.unwrap();
+
+        assert_eq!(verified, claims);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_claims() {
+        let verifier = build_verifier();
+        let expired = Claims {
+            exp: jsonwebtoken::get_current_timestamp() - 300,
+            ..Claims::valid()
+        };
+        let other_issuer = Claims {
+            iss: "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_other".to_string(),
+            ..Claims::valid()
+        };
+        let other_audience = Claims {
+            aud: "other-client".to_string(),
+            ..Claims::valid()
+        };
+
+        for claims in [expired, other_issuer, other_audience] {
+            let token = sign(&claims, PRIVATE_KEY);
+            assert!(verifier.verify::<Claims>(&token).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forged_tokens() {
+        let verifier = build_verifier();
+        let claims = Claims::valid();
+
+        let token = sign(&claims, UNKNOWN_PRIVATE_KEY);
+        assert!(verifier.verify::<Claims>(&token).await.is_err());
+
+        // the algorithm in the header is not trusted
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("test-key-1".to_string());
+        let token = encode(&header, &claims, &EncodingKey::from_secret(b"secret")).unwrap();
+        assert!(verifier.verify::<Claims>(&token).await.is_err());
+
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some("unknown-key".to_string());
+        let key = EncodingKey::from_rsa_pem(PRIVATE_KEY).unwrap();
+        let token = encode(&header, &claims, &key).unwrap();
+        assert!(verifier.verify::<Claims>(&token).await.is_err());
+    }
+
+    #[test]
+    fn test_jwks_url_for_issuer() {
+        assert_eq!(
+            JwksSource::for_issuer(&format!("{}/", ISSUER)),
+            JwksSource::Url(format!("{}/.well-known/jwks.json", ISSUER))
+        );
+    }
+}
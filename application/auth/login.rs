@@ -0,0 +1,155 @@
+//! The endpoints the web frontend logs in with when an OIDC provider is configured.
+//!
+//! `/auth/login` sends the user to the provider, and `/auth/callback` is where they come
+//! back to. The user is then created or brought up to date, and sent on to the frontend
+//! with a session in the URL fragment, which the frontend sends to the GraphQL API.
+use anyhow::{anyhow, bail};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Redirect, Response};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::auth::oidc::{DynIdentityProvider, LoginRequest};
+use crate::auth::session::{SessionKeys, LOGIN_DURATION};
+use crate::repository::DynRepository;
+
+const LOGIN_COOKIE: &str = "raktar_login";
+
+#[derive(Clone)]
+pub struct LoginState {
+    pub repository: DynRepository,
+    pub provider: DynIdentityProvider,
+    pub sessions: SessionKeys,
+    pub frontend_url: String,
+}
+
+pub async fn login(State(state): State<LoginState>) -> Response {
+    let login = LoginRequest::generate();
+    match state.provider.authorization_url(&login).await {
+        Ok(url) => {
+            let cookie = format!(
+                "{}={}; Path=/auth; Max-Age={}; HttpOnly; Secure; SameSite=Lax",
+                LOGIN_COOKIE,
+                state.sessions.seal_login(&login),
+                LOGIN_DURATION.as_secs()
+            );
+            ([(header::SET_COOKIE, cookie)], Redirect::to(&url)).into_response()
+        }
+        Err(err) => {
+            let error_message = err.to_string();
+            error!(error_message, "failed to start logging in");
+            (
+                StatusCode::BAD_GATEWAY,
+                "failed to reach the identity provider",
+            )
+                .into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackParams {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+pub async fn login_callback(
+    State(state): State<LoginState>,
+    Query(params): Query<CallbackParams>,
+    headers: HeaderMap,
+) -> Response {
+    let clear_cookie = format!(
+        "{}=; Path=/auth; Max-Age=0; HttpOnly; Secure; SameSite=Lax",
+        LOGIN_COOKIE
+    );
+    match complete_login(&state, params, &headers).await {
+        Ok(session) => {
+            let url = format!(
+                "{}/#session={}",
+                state.frontend_url.trim_end_matches('/'),
+                session
+            );
+            ([(header::SET_COOKIE, clear_cookie)], Redirect::to(&url)).into_response()
+        }
+        Err(err) => {
+            warn!(error_message = err.to_string(), "failed to log in");
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::SET_COOKIE, clear_cookie)],
+                "failed to log in",
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Checks that the callback belongs to the login that was started, and gives back
+/// a session for the user.
+async fn complete_login(
+    state: &LoginState,
+    params: CallbackParams,
+    headers: &HeaderMap,
+) -> anyhow::Result<String> {
+    if let Some(error) = params.error {
+        bail!(
+            "the provider returned {}: {}",
+            error,
+            params.error_description.unwrap_or_default()
+        );
+    }
+    let sealed = get_cookie(headers, LOGIN_COOKIE)
+        .ok_or_else(|| anyhow!("the login was not started from this browser"))?;
+    let login = state.sessions.open_login(sealed)?;
+    if params.state.as_deref() != Some(login.state.as_str()) {
+        bail!("the state does not match the login");
+    }
+    let code = params
+        .code
+        .ok_or_else(|| anyhow!("the provider returned no code"))?;
+
+    let claims = state.provider.exchange_code(&code, &login).await?;
+    if claims.nonce.as_deref() != Some(login.nonce.as_str()) {
+        bail!("the ID token was not issued for this login");
+    }
+    let user = state
+        .repository
+        .update_or_create_user(claims.into_user_data()?)
+        .await?;
+    info!(login = user.login, id = user.id, "user logged in");
+
+    Ok(state.sessions.issue_session(user.id))
+}
+
+fn get_cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_get_cookie() {
+        let mut headers = HeaderMap::new();
+        headers.append(header::COOKIE, HeaderValue::from_static("theme=dark"));
+        headers.append(
+            header::COOKIE,
+            HeaderValue::from_static("other=1; raktar_login=sealed"),
+        );
+
+        assert_eq!(get_cookie(&headers, "raktar_login"), Some("sealed"));
+        assert_eq!(get_cookie(&headers, "theme"), Some("dark"));
+        assert_eq!(get_cookie(&headers, "missing"), None);
+    }
+}
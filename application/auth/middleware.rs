@@ -1,35 +1,188 @@
-use axum::extract::State;
+use anyhow::{anyhow, bail};
+use axum::extract::{Path, State};
 use axum::http::{Request, StatusCode};
 use axum::middleware::Next;
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use tracing::{error, warn};
 
-use crate::auth::AuthenticatedUser;
-use crate::repository::DynRepository;
+use crate::auth::asymmetric::{Claims, Footer, Mutation, MAX_TOKEN_AGE};
+use crate::auth::paseto::{SignedToken, TOKEN_HEADER};
+use crate::auth::{AuthenticatedUser, PublicKey};
+use crate::error::AppError;
+use crate::models::token::Token;
+use crate::router::AppState;
 
+/// Authenticates requests that change the registry.
+///
+/// Tokens limited to certain crates are turned away here when the crate is part of the path,
+/// the handlers check the rest of the token's scopes.
 pub async fn token_authenticator<B>(
-    State(repository): State<DynRepository>,
+    State(state): State<AppState>,
+    params: Option<Path<HashMap<String, String>>>,
     mut request: Request<B>,
     next: Next<B>,
-) -> impl IntoResponse {
-    if let Some(auth_header) = request.headers().get("Authorization") {
-        let token = auth_header.as_bytes();
-        match repository.get_auth_token(token).await {
-            Ok(Some(t)) => {
-                let user = AuthenticatedUser { id: t.user_id };
-                request.extensions_mut().insert(user);
-                return next.run(request).await;
+) -> Response {
+    let params = params.map(|Path(params)| params).unwrap_or_default();
+    let Some(user) = authenticate(&state, &request, &params).await else {
+        warn!("unauthorized attempt to access registry");
+        return unauthorized();
+    };
+
+    if let Some(crate_name) = params.get("crate_name") {
+        if !user.scopes.allows_crate(crate_name) {
+            warn!(
+                user_id = user.id,
+                crate_name, "token is not allowed to change crate"
+            );
+            let detail = format!("this token is not allowed to change {}", crate_name);
+            return AppError::Forbidden(detail).into_response();
+        }
+    }
+
+    request.extensions_mut().insert(user);
+    next.run(request).await
+}
+
+/// Authenticates requests that only read from the registry.
+///
+/// Requests with a token are authenticated the same way as in [`token_authenticator`].
+/// Requests without a token are let through if the registry is configured to allow
+/// anonymous reads of the requested crate.
+pub async fn read_authenticator<B>(
+    State(state): State<AppState>,
+    Path(params): Path<HashMap<String, String>>,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    if request.headers().contains_key("Authorization") {
+        if let Some(user) = authenticate(&state, &request, &params).await {
+            request.extensions_mut().insert(user);
+            return next.run(request).await;
+        }
+    } else if let Some(crate_name) = params.get("crate_name") {
+        if state.config.anonymous_read.allows(crate_name) {
+            return next.run(request).await;
+        }
+    }
+
+    warn!("unauthorized attempt to read from registry");
+    unauthorized()
+}
+
+async fn authenticate<B>(
+    state: &AppState,
+    request: &Request<B>,
+    params: &HashMap<String, String>,
+) -> Option<AuthenticatedUser> {
+    let auth_header = request.headers().get("Authorization")?.as_bytes();
+    let now = Utc::now();
+
+    // asymmetric tokens are stored under the id of their key, and can't be sent as is
+    let (token, token_key, signed_claims) = if auth_header.starts_with(TOKEN_HEADER.as_bytes()) {
+        match authenticate_signed(state, request, params, auth_header, now).await {
+            Ok((token, key_id, claims)) => (token, key_id.into_bytes(), Some(claims)),
+            Err(err) => {
+                warn!(err = err.to_string(), "invalid signed token");
+                return None;
             }
+        }
+    } else {
+        let token = match state.repository.get_auth_token(auth_header).await {
+            Ok(token) => token?,
             Err(err) => {
                 error!(
                     err = err.to_string(),
                     "error in trying to get token for user"
                 );
+                return None;
             }
-            _ => {}
+        };
+        if token.public_key.is_some() {
+            warn!(
+                token_id = token.token_id,
+                "attempt to use the id of a public key as a token"
+            );
+            return None;
+        }
+        (token, auth_header.to_vec(), None)
+    };
+
+    if token.is_expired(now) {
+        warn!(
+            user_id = token.user_id,
+            token_id = token.token_id,
+            "attempt to use expired token"
+        );
+        return None;
+    }
+
+    // failing to record the use shouldn't fail the request
+    if token.needs_last_used_update(now) {
+        if let Err(err) = state.repository.record_token_use(&token_key, now).await {
+            error!(
+                err = err.to_string(),
+                token_id = token.token_id,
+                "error in recording token use"
+            );
         }
     }
 
-    warn!("unauthorized attempt to access registry");
+    Some(AuthenticatedUser {
+        id: token.user_id,
+        scopes: token.scopes,
+        signed_claims,
+    })
+}
+
+/// Verifies a token Cargo signed for this request with a registered key,
+/// giving back the key's token along with the id it's stored under.
+async fn authenticate_signed<B>(
+    state: &AppState,
+    request: &Request<B>,
+    params: &HashMap<String, String>,
+    auth_header: &[u8],
+    now: DateTime<Utc>,
+) -> anyhow::Result<(Token, String, Claims)> {
+    let signed = SignedToken::parse(std::str::from_utf8(auth_header)?)?;
+    let footer = Footer::parse(signed.footer())?;
+    footer.check_url(&state.config.index_url)?;
+
+    let token = state
+        .repository
+        .get_auth_token(footer.kip.as_bytes())
+        .await?
+        .ok_or_else(|| anyhow!("no public key is registered with id {}", footer.kip))?;
+    let public_key: PublicKey = token
+        .public_key
+        .as_deref()
+        .ok_or_else(|| anyhow!("{} is not the id of a public key", footer.kip))?
+        .parse()?;
+    let claims = Claims::parse(signed.verify(&public_key)?)?;
+
+    let allowed_mutations = Mutation::allowed_for_request(request.method(), request.uri().path());
+    claims.check_request(
+        allowed_mutations,
+        params.get("crate_name").map(String::as_str),
+        params.get("version").map(String::as_str),
+        now,
+    )?;
+
+    // tokens for reading may be reused by Cargo for a while, but a change is signed every time
+    if claims.mutation.is_some() {
+        let first_use = state
+            .repository
+            .claim_signed_token(auth_header, claims.iat + MAX_TOKEN_AGE)
+            .await?;
+        if !first_use {
+            bail!("the token was used before");
+        }
+    }
+
+    Ok((token, footer.kip, claims))
+}
+
+fn unauthorized() -> Response {
     (StatusCode::UNAUTHORIZED, "Unauthorized".to_string()).into_response()
 }
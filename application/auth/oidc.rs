@@ -0,0 +1,405 @@
+//! Logging in to the web frontend with any OpenID Connect provider, such as Keycloak,
+//! Okta, Azure AD or Dex, instead of going through Cognito.
+//!
+//! Users are sent to the provider with the authorization code flow and PKCE, and the
+//! code they come back with is exchanged for an ID token, whose claims say who they are.
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::RngCore;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
+use tracing::info;
+use url::Url;
+
+use crate::auth::client;
+use crate::auth::jwt::{JwksSource, JwtConfig, JwtVerifier};
+use crate::models::user::CognitoUserData;
+
+/// The claims asked for on top of `openid`, which most providers need to include
+/// the email and name in the ID token.
+const SCOPES: &str = "openid email profile";
+
+#[derive(Clone, PartialEq)]
+pub struct OidcConfig {
+    /// The issuer URL of the provider, which its configuration is discovered from.
+    pub issuer: String,
+    pub client_id: String,
+    /// The secret of confidential clients, public clients only rely on PKCE.
+    pub client_secret: Option<String>,
+    /// Where the provider sends users back to, the registry's `/auth/callback`.
+    pub redirect_url: String,
+    /// Where users end up once they're logged in.
+    pub frontend_url: String,
+    /// The secret the registry signs its sessions with.
+    pub session_secret: String,
+}
+
+/// Leaves the secrets out, so that the configuration can be logged.
+impl fmt::Debug for OidcConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OidcConfig")
+            .field("issuer", &self.issuer)
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("redirect_url", &self.redirect_url)
+            .field("frontend_url", &self.frontend_url)
+            .field("session_secret", &"<redacted>")
+            .finish()
+    }
+}
+
+/// What's kept between sending the user to the provider and them coming back.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LoginRequest {
+    /// Ties the callback to the browser that started logging in.
+    pub state: String,
+    /// Ties the ID token to this login.
+    pub nonce: String,
+    /// The PKCE secret, which the provider only sees the hash of until the code is exchanged.
+    pub code_verifier: String,
+}
+
+impl LoginRequest {
+    pub fn generate() -> Self {
+        Self {
+            state: generate_secret(),
+            nonce: generate_secret(),
+            code_verifier: generate_secret(),
+        }
+    }
+
+    /// The S256 PKCE challenge for the verifier.
+    pub fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(&self.code_verifier))
+    }
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// The standard claims of the ID token that users are created from.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct IdentityClaims {
+    /// The provider's id of the user.
+    pub sub: String,
+    pub email: Option<String>,
+    /// Whether the provider checked that the email belongs to the user, which some
+    /// providers such as Cognito send as a string.
+    #[serde(default, deserialize_with = "deserialize_flag")]
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub nonce: Option<String>,
+}
+
+impl IdentityClaims {
+    /// The user the claims describe.
+    ///
+    /// Users log in with their email like they do through Cognito, so it has to be verified
+    /// by the provider, otherwise anyone could sign up with someone else's email and log in
+    /// as them. Providers that only give a full name have it split at the first space.
+    pub fn into_user_data(self) -> anyhow::Result<CognitoUserData> {
+        let login = match (self.email, self.email_verified) {
+            (Some(email), Some(true)) => email,
+            (Some(email), _) => bail!("the email {} is not verified", email),
+            (None, _) => bail!("the ID token of {} has no email", self.sub),
+        };
+        let (given_name, family_name) = match (self.given_name, self.family_name, self.name) {
+            (Some(given_name), family_name, _) => (given_name, family_name.unwrap_or_default()),
+            (None, Some(family_name), _) => (String::new(), family_name),
+            (None, None, Some(name)) => match name.trim().split_once(' ') {
+                Some((given_name, family_name)) => {
+                    (given_name.to_string(), family_name.trim().to_string())
+                }
+                None => (name.trim().to_string(), String::new()),
+            },
+            (None, None, None) => (login.clone(), String::new()),
+        };
+
+        Ok(CognitoUserData {
+            login,
+            given_name,
+            family_name,
+        })
+    }
+}
+
+fn deserialize_flag<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Flag {
+        Bool(bool),
+        String(String),
+    }
+
+    match Option::<Flag>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Flag::Bool(flag)) => Ok(Some(flag)),
+        Some(Flag::String(flag)) => match flag.as_str() {
+            "true" => Ok(Some(true)),
+            "false" => Ok(Some(false)),
+            _ => Err(D::Error::custom(format!("invalid flag {}", flag))),
+        },
+    }
+}
+
+#[async_trait::async_trait]
+pub trait IdentityProvider {
+    /// Where to send the user to log in.
+    async fn authorization_url(&self, login: &LoginRequest) -> anyhow::Result<String>;
+    /// Exchanges the code the user came back with for the verified claims of their ID token.
+    async fn exchange_code(
+        &self,
+        code: &str,
+        login: &LoginRequest,
+    ) -> anyhow::Result<IdentityClaims>;
+}
+
+pub type DynIdentityProvider = Arc<dyn IdentityProvider + Send + Sync>;
+
+/// A provider whose endpoints and keys are discovered from its issuer URL.
+pub struct OidcProvider {
+    config: OidcConfig,
+    discovered: OnceCell<DiscoveredProvider>,
+}
+
+/// The parts of the provider's configuration that are used, see
+/// https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata
+#[derive(Debug, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct DiscoveredProvider {
+    metadata: ProviderMetadata,
+    verifier: JwtVerifier,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+impl OidcProvider {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            discovered: OnceCell::new(),
+        }
+    }
+
+    /// Discovers the provider the first time it's needed, trying again on the next login
+    /// if that fails.
+    async fn discover(&self) -> anyhow::Result<&DiscoveredProvider> {
+        self.discovered
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.config.issuer.trim_end_matches('/')
+                );
+                let metadata: ProviderMetadata = serde_json::from_slice(&client::get(&url).await?)?;
+                if metadata.issuer != self.config.issuer {
+                    bail!("the provider's issuer is {}", metadata.issuer);
+                }
+                info!(issuer = metadata.issuer, "discovered OIDC provider");
+
+                let verifier = JwtVerifier::new(JwtConfig {
+                    issuer: self.config.issuer.clone(),
+                    audience: self.config.client_id.clone(),
+                    jwks: JwksSource::Url(metadata.jwks_uri.clone()),
+                });
+
+                Ok(DiscoveredProvider { metadata, verifier })
+            })
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityProvider for OidcProvider {
+    async fn authorization_url(&self, login: &LoginRequest) -> anyhow::Result<String> {
+        let provider = self.discover().await?;
+        let url = Url::parse_with_params(
+            &provider.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.config.client_id),
+                ("redirect_uri", &self.config.redirect_url),
+                ("scope", SCOPES),
+                ("state", &login.state),
+                ("nonce", &login.nonce),
+                ("code_challenge", &login.code_challenge()),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+
+        Ok(url.into())
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        login: &LoginRequest,
+    ) -> anyhow::Result<IdentityClaims> {
+        let provider = self.discover().await?;
+        let credentials = self
+            .config
+            .client_secret
+            .as_deref()
+            .map(|secret| (self.config.client_id.as_str(), secret));
+        let body = client::post_form(
+            &provider.metadata.token_endpoint,
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("client_id", &self.config.client_id),
+                ("code_verifier", &login.code_verifier),
+            ],
+            credentials,
+        )
+        .await?;
+        let response: TokenResponse = serde_json::from_slice(&body)
+            .map_err(|_| anyhow!("the token response has no ID token"))?;
+
+        provider.verifier.verify(&response.id_token).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_claims() -> IdentityClaims {
+        IdentityClaims {
+            sub: "248289761001".to_string(),
+            email: Some("jane@raktar.io".to_string()),
+            email_verified: Some(true),
+            name: None,
+            given_name: None,
+            family_name: None,
+            nonce: None,
+        }
+    }
+
+    #[test]
+    fn test_code_challenge() {
+        // from the example in RFC 7636
+        let login = LoginRequest {
+            state: "state".to_string(),
+            nonce: "nonce".to_string(),
+            code_verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".to_string(),
+        };
+
+        assert_eq!(
+            login.code_challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+
+    #[test]
+    fn test_generated_logins_differ() {
+        let login = LoginRequest::generate();
+        let other = LoginRequest::generate();
+
+        assert_eq!(login.code_verifier.len(), 43);
+        assert_ne!(login.state, other.state);
+        assert_ne!(login.nonce, other.nonce);
+        assert_ne!(login.code_verifier, other.code_verifier);
+    }
+
+    #[test]
+    fn test_user_data_from_given_and_family_name() {
+        let claims = IdentityClaims {
+            name: Some("Jane Doe".to_string()),
+            given_name: Some("Jane".to_string()),
+            family_name: Some("Van Doe".to_string()),
+            ..build_claims()
+        };
+
+        let user_data = claims.into_user_data().unwrap();
+
+        assert_eq!(
+            user_data,
+            CognitoUserData {
+                login: "jane@raktar.io".to_string(),
+                given_name: "Jane".to_string(),
+                family_name: "Van Doe".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_user_data_from_full_name() {
+        let claims = IdentityClaims {
+            name: Some("Jane Van Doe".to_string()),
+            ..build_claims()
+        };
+
+        let user_data = claims.into_user_data().unwrap();
+
+        assert_eq!(user_data.given_name, "Jane");
+        assert_eq!(user_data.family_name, "Van Doe");
+    }
+
+    #[test]
+    fn test_user_data_without_name() {
+        let user_data = build_claims().into_user_data().unwrap();
+
+        assert_eq!(user_data.login, "jane@raktar.io");
+        assert_eq!(user_data.given_name, "jane@raktar.io");
+        assert_eq!(user_data.family_name, "");
+    }
+
+    #[test]
+    fn test_unverified_email_is_rejected() {
+        for email_verified in [None, Some(false)] {
+            let claims = IdentityClaims {
+                email_verified,
+                ..build_claims()
+            };
+
+            let err = claims.into_user_data().unwrap_err();
+
+            assert_eq!(err.to_string(), "the email jane@raktar.io is not verified");
+        }
+
+        let claims = IdentityClaims {
+            email: None,
+            ..build_claims()
+        };
+        assert!(claims.into_user_data().is_err());
+    }
+
+    #[test]
+    fn test_email_verified_as_string() {
+        let claims: IdentityClaims = serde_json::from_str(
+            r#"{"sub":"248289761001","email":"jane@raktar.io","email_verified":"true"}"#,
+        )
+        .unwrap();
+        assert_eq!(claims.email_verified, Some(true));
+
+        let claims: IdentityClaims =
+            serde_json::from_str(r#"{"sub":"248289761001","email_verified":false}"#).unwrap();
+        assert_eq!(claims.email_verified, Some(false));
+
+        let claims: IdentityClaims = serde_json::from_str(r#"{"sub":"248289761001"}"#).unwrap();
+        assert_eq!(claims.email_verified, None);
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::auth::CratePattern;
+use crate::error::{AppError, AppResult};
+
+/// The endpoints a token can be limited to, named the same way as the crates.io token scopes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndpointScope {
+    /// Publishing the first version of a crate.
+    PublishNew,
+    /// Publishing a new version of an existing crate.
+    PublishUpdate,
+    /// Yanking and unyanking versions.
+    Yank,
+    ChangeOwners,
+}
+
+impl EndpointScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EndpointScope::PublishNew => "publish-new",
+            EndpointScope::PublishUpdate => "publish-update",
+            EndpointScope::Yank => "yank",
+            EndpointScope::ChangeOwners => "change-owners",
+        }
+    }
+}
+
+impl FromStr for EndpointScope {
+    type Err = anyhow::Error;
+
+    fn from_str(scope: &str) -> Result<Self, Self::Err> {
+        match scope {
+            "publish-new" => Ok(EndpointScope::PublishNew),
+            "publish-update" => Ok(EndpointScope::PublishUpdate),
+            "yank" => Ok(EndpointScope::Yank),
+            "change-owners" => Ok(EndpointScope::ChangeOwners),
+            _ => bail!("invalid endpoint scope {}", scope),
+        }
+    }
+}
+
+impl fmt::Display for EndpointScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// What a token is allowed to do.
+///
+/// Scopes that are not set don't restrict anything, which is also how tokens created
+/// before scopes existed behave.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TokenScopes {
+    pub endpoints: Option<Vec<EndpointScope>>,
+    pub crates: Option<Vec<CratePattern>>,
+}
+
+impl TokenScopes {
+    pub fn allows_crate(&self, crate_name: &str) -> bool {
+        self.crates
+            .as_ref()
+            .is_none_or(|patterns| patterns.iter().any(|p| p.matches(crate_name)))
+    }
+
+    pub fn allows(&self, endpoint: EndpointScope, crate_name: &str) -> bool {
+        let allows_endpoint = self
+            .endpoints
+            .as_ref()
+            .is_none_or(|endpoints| endpoints.contains(&endpoint));
+
+        allows_endpoint && self.allows_crate(crate_name)
+    }
+
+    pub fn check(&self, endpoint: EndpointScope, crate_name: &str) -> AppResult<()> {
+        if self.allows(endpoint, crate_name) {
+            return Ok(());
+        }
+
+        Err(AppError::Forbidden(format!(
+            "this token does not have the {} scope for {}",
+            endpoint, crate_name
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scopes(endpoints: Option<&[EndpointScope]>, crates: Option<&[&str]>) -> TokenScopes {
+        TokenScopes {
+            endpoints: endpoints.map(|e| e.to_vec()),
+            crates: crates.map(|c| c.iter().map(|p| p.parse().unwrap()).collect()),
+        }
+    }
+
+    #[test]
+    fn test_unscoped_token_allows_everything() {
+        let scopes = TokenScopes::default();
+
+        assert!(scopes.allows(EndpointScope::PublishNew, "foo"));
+        assert!(scopes.allows(EndpointScope::ChangeOwners, "bar"));
+    }
+
+    #[test]
+    fn test_endpoint_scopes() {
+        let scopes = scopes(Some(&[EndpointScope::PublishUpdate]), None);
+
+        assert!(scopes.allows(EndpointScope::PublishUpdate, "foo"));
+        assert!(!scopes.allows(EndpointScope::PublishNew, "foo"));
+        assert!(!scopes.allows(EndpointScope::Yank, "foo"));
+    }
+
+    #[test]
+    fn test_crate_scopes() {
+        let scopes = scopes(Some(&[EndpointScope::Yank]), Some(&["foo-*", "bar"]));
+
+        assert!(scopes.allows(EndpointScope::Yank, "foo-core"));
+        assert!(scopes.allows(EndpointScope::Yank, "bar"));
+        assert!(!scopes.allows(EndpointScope::Yank, "baz"));
+        assert!(!scopes.allows(EndpointScope::PublishUpdate, "bar"));
+        assert!(matches!(
+            scopes.check(EndpointScope::Yank, "baz"),
+            Err(AppError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn test_endpoint_scope_names() {
+        for name in ["publish-new", "publish-update", "yank", "change-owners"] {
+            assert_eq!(EndpointScope::from_str(name).unwrap().to_string(), name);
+        }
+        assert!(EndpointScope::from_str("publish").is_err());
+    }
+}
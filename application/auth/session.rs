@@ -0,0 +1,139 @@
+//! The sessions the registry issues itself once users log in with an OIDC provider.
+//!
+//! Sessions are JWTs signed with the registry's own secret, so they can be sent to the
+//! GraphQL API just like the tokens from Cognito. The same secret seals the login request
+//! that's kept in a cookie while the user is at the provider.
+use jsonwebtoken::{
+    decode, encode, get_current_timestamp, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::auth::oidc::LoginRequest;
+use crate::models::user::UserId;
+
+/// How long users stay logged in.
+pub const SESSION_DURATION: Duration = Duration::from_secs(12 * 60 * 60);
+/// How long users have to log in at the provider.
+pub const LOGIN_DURATION: Duration = Duration::from_secs(10 * 60);
+/// Keeps sessions and login requests from being used in place of each other.
+const SESSION_AUDIENCE: &str = "raktar-session";
+const LOGIN_AUDIENCE: &str = "raktar-login";
+
+#[derive(Clone)]
+pub struct SessionKeys {
+    /// The registry's API URL, which sessions are issued by.
+    issuer: String,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SignedClaims<T> {
+    iss: String,
+    aud: String,
+    exp: u64,
+    #[serde(flatten)]
+    content: T,
+}
+
+#[derive(Deserialize, Serialize)]
+struct SessionClaims {
+    sub: String,
+}
+
+impl SessionKeys {
+    pub fn new(issuer: &str, secret: &[u8]) -> Self {
+        Self {
+            issuer: issuer.to_string(),
+            encoding_key: EncodingKey::from_secret(secret),
+            decoding_key: DecodingKey::from_secret(secret),
+        }
+    }
+
+    pub fn issue_session(&self, user_id: UserId) -> String {
+        let claims = SessionClaims {
+            sub: user_id.to_string(),
+        };
+
+        self.sign(SESSION_AUDIENCE, SESSION_DURATION, claims)
+    }
+
+    /// Gives back the user the session was issued for.
+    pub fn verify_session(&self, token: &str) -> anyhow::Result<UserId> {
+        let claims: SessionClaims = self.verify(SESSION_AUDIENCE, token)?;
+
+        Ok(claims.sub.parse()?)
+    }
+
+    pub fn seal_login(&self, login: &LoginRequest) -> String {
+        self.sign(LOGIN_AUDIENCE, LOGIN_DURATION, login)
+    }
+
+    pub fn open_login(&self, sealed: &str) -> anyhow::Result<LoginRequest> {
+        self.verify(LOGIN_AUDIENCE, sealed)
+    }
+
+    fn sign<T: Serialize>(&self, audience: &str, duration: Duration, content: T) -> String {
+        let claims = SignedClaims {
+            iss: self.issuer.clone(),
+            aud: audience.to_string(),
+            exp: get_current_timestamp() + duration.as_secs(),
+            content,
+        };
+
+        encode(&Header::new(Algorithm::HS256), &claims, &self.encoding_key)
+            .expect("HS256 signing to never fail")
+    }
+
+    fn verify<T: DeserializeOwned>(&self, audience: &str, token: &str) -> anyhow::Result<T> {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.set_issuer(&[&self.issuer]);
+        validation.set_audience(&[audience]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud"]);
+        let data = decode::<SignedClaims<T>>(token, &self.decoding_key, &validation)?;
+
+        Ok(data.claims.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_keys() -> SessionKeys {
+        SessionKeys::new(
+            "https://api.raktar.io",
+            b"a secret that is long enough to use",
+        )
+    }
+
+    #[test]
+    fn test_session() {
+        let keys = build_keys();
+
+        let session = keys.issue_session(42);
+
+        assert_eq!(keys.verify_session(&session).unwrap(), 42);
+        let other_keys = SessionKeys::new("https://api.raktar.io", b"another secret");
+        assert!(other_keys.verify_session(&session).is_err());
+        let other_issuer = SessionKeys::new(
+            "https://api.other.io",
+            b"a secret that is long enough to use",
+        );
+        assert!(other_issuer.verify_session(&session).is_err());
+    }
+
+    #[test]
+    fn test_sealed_login() {
+        let keys = build_keys();
+        let login = LoginRequest::generate();
+
+        let sealed = keys.seal_login(&login);
+
+        assert_eq!(keys.open_login(&sealed).unwrap(), login);
+        assert!(keys.verify_session(&sealed).is_err());
+        assert!(keys.open_login(&keys.issue_session(42)).is_err());
+    }
+}
@@ -1,4 +1,23 @@
+use crate::auth::asymmetric::Claims;
+use crate::auth::TokenScopes;
+
 #[derive(Clone, Debug)]
 pub struct AuthenticatedUser {
     pub id: u32,
+    /// What the token the user authenticated with allows, users that signed in
+    /// to the web frontend are not restricted.
+    pub scopes: TokenScopes,
+    /// What Cargo signed the request for, when it was made with an asymmetric token.
+    pub signed_claims: Option<Claims>,
+}
+
+impl AuthenticatedUser {
+    /// A user that's allowed to do everything they have permission for.
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            scopes: TokenScopes::default(),
+            signed_claims: None,
+        }
+    }
 }
@@ -1,9 +1,10 @@
-use axum::http::StatusCode;
+use axum::extract::State;
 use axum::Json;
 use serde::Serialize;
-use serde_json::{json, Value};
-use tracing::error;
 
+use crate::router::AppState;
+
+/// The `config.json` file at the root of the index.
 #[derive(Serialize)]
 pub struct Config {
     dl: String,
@@ -12,32 +13,10 @@ pub struct Config {
     auth_required: bool,
 }
 
-#[derive(Serialize)]
-#[serde(untagged)]
-pub enum Response {
-    Config(Config),
-    Error(Value),
-}
-
-pub async fn get_config_json() -> (StatusCode, Json<Response>) {
-    if let Ok(domain_name) = std::env::var("DOMAIN_NAME") {
-        let dl = format!("https://{}/api/v1/crates", domain_name);
-        let api = format!("https://{}", domain_name);
-        let config = Config {
-            dl,
-            api,
-            auth_required: true,
-        };
-
-        (StatusCode::OK, Json(Response::Config(config)))
-    } else {
-        error!("DOMAIN_NAME is not set in environment");
-        let error_response = json!({
-            "reason": "misconfigured application"
-        });
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(Response::Error(error_response)),
-        )
-    }
+pub async fn get_config_json(State(AppState { config, .. }): State<AppState>) -> Json<Config> {
+    Json(Config {
+        dl: config.download_url_template.clone(),
+        api: config.api_url.clone(),
+        auth_required: config.auth_required,
+    })
 }
@@ -1,15 +1,66 @@
+mod checksum;
+pub mod counter;
+
 use std::str::FromStr;
 
+use axum::body::Bytes;
 use axum::extract::{Path, State};
+use axum::response::{IntoResponse, Response};
+use http::header::{CACHE_CONTROL, CONTENT_LENGTH, CONTENT_TYPE, LOCATION};
+use http::{HeaderValue, StatusCode};
 use semver::Version;
 
-use crate::error::AppResult;
+use crate::cargo_api::download::checksum::verify_checksum;
+use crate::error::{internal_error, AppError, AppResult};
 use crate::router::AppState;
 
+/// A published version never changes, so clients can keep it for as long as they like.
+/// Downloads can require a token, so only the client itself is allowed to cache them.
+const CACHE_CONTROL_VALUE: &str = "private, max-age=31536000, immutable";
+
 pub async fn download_crate(
     Path((crate_name, version)): Path<(String, String)>,
-    State((_, storage)): State<AppState>,
-) -> AppResult<Vec<u8>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
-    storage.get_crate(&crate_name, vers).await
+    State(AppState {
+        repository,
+        storage,
+        downloads,
+        ..
+    }): State<AppState>,
+) -> AppResult<Response> {
+    let vers = Version::from_str(&version).map_err(|_| AppError::InvalidVersion(version))?;
+    // yanked versions can still be downloaded, as lockfiles may refer to them
+    let Some(package_info) = repository
+        .get_package_version_info(&crate_name, &vers)
+        .await?
+    else {
+        return Err(AppError::NonExistentCrateVersion {
+            crate_name,
+            version: vers,
+        });
+    };
+
+    if let Some(url) = storage.get_download_url(&crate_name, &vers).await? {
+        // the client downloads the crate from the storage backend directly, so it can't be
+        // verified here, and the URL expires, so the redirect itself must not be cached
+        let headers = [
+            (
+                LOCATION,
+                HeaderValue::try_from(url).map_err(|_| internal_error())?,
+            ),
+            (CACHE_CONTROL, HeaderValue::from_static("no-store")),
+        ];
+        downloads.record(&crate_name, &vers);
+        return Ok((StatusCode::FOUND, headers).into_response());
+    }
+
+    let body = storage.get_crate(&crate_name, vers.clone()).await?;
+    let data = verify_checksum(body, &crate_name, &vers, &package_info.cksum).await?;
+    downloads.record(&crate_name, &vers);
+
+    let headers = [
+        (CONTENT_TYPE, HeaderValue::from_static("application/gzip")),
+        (CONTENT_LENGTH, HeaderValue::from(data.len())),
+        (CACHE_CONTROL, HeaderValue::from_static(CACHE_CONTROL_VALUE)),
+    ];
+    Ok((headers, Bytes::from(data)).into_response())
 }
@@ -0,0 +1,110 @@
+//! Verifies crates against the checksum in their index entry before they are sent.
+//!
+//! The whole crate has to be read before the checksum is known, so it is read into
+//! memory first, and a corrupted crate is answered with an error status instead of
+//! a body. Published crates are small, so holding one in memory is cheap.
+use anyhow::anyhow;
+use hex::ToHex;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+use crate::error::AppResult;
+use crate::storage::CrateBody;
+
+pub async fn verify_checksum(
+    body: CrateBody,
+    crate_name: &str,
+    version: &Version,
+    expected_checksum: &str,
+) -> AppResult<Vec<u8>> {
+    let data = body.into_bytes().await.map_err(|err| {
+        let error_message = err.to_string();
+        error!(
+            error_message,
+            crate_name,
+            version = version.to_string(),
+            "failed to read stored crate"
+        );
+        anyhow!("unexpected error in reading crate")
+    })?;
+
+    let checksum: String = Sha256::digest(&data).encode_hex();
+    if checksum != expected_checksum {
+        // logged with a fixed error type, so that corrupted crates can be counted and alerted on
+        let error_type = "checksum_mismatch".to_string();
+        error!(
+            error_type,
+            crate_name,
+            version = version.to_string(),
+            expected_checksum,
+            actual_checksum = checksum,
+            "stored crate does not match the checksum in the index"
+        );
+        return Err(anyhow!("stored crate does not match its checksum").into());
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use axum::body::Bytes;
+    use futures::stream;
+    use std::io::{self, ErrorKind};
+
+    fn checksum(data: &[u8]) -> String {
+        Sha256::digest(data).encode_hex()
+    }
+
+    #[tokio::test]
+    async fn test_matching_crate_is_returned() {
+        let body = CrateBody::from_bytes(b"crate".to_vec());
+
+        let data = verify_checksum(body, "foo", &Version::new(0, 1, 0), &checksum(b"crate"))
+            .await
+            .unwrap();
+
+        assert_eq!(data, b"crate");
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_crate_fails() {
+        let chunks = [b"tam".as_slice(), b"per".as_slice(), b"ed".as_slice()]
+            .map(|chunk| Ok(Bytes::from_static(chunk)));
+        let body = CrateBody {
+            content_length: 8,
+            stream: Box::pin(stream::iter(chunks)),
+        };
+
+        let err = verify_checksum(body, "foo", &Version::new(0, 1, 0), &checksum(b"crate"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, AppError::Anyhow(_)));
+        assert_eq!(err.to_string(), "stored crate does not match its checksum");
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_crate_fails() {
+        let chunks = [
+            Ok(Bytes::from_static(b"cra")),
+            Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "stored crate is corrupted",
+            )),
+        ];
+        let body = CrateBody {
+            content_length: 5,
+            stream: Box::pin(stream::iter(chunks)),
+        };
+
+        let err = verify_checksum(body, "foo", &Version::new(0, 1, 0), &checksum(b"crate"))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "unexpected error in reading crate");
+    }
+}
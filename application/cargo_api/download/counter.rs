@@ -0,0 +1,175 @@
+//! Counts downloads without making them wait for the repository.
+//!
+//! Downloads are added up in memory per crate version and day, and written to the
+//! repository in batches. Counts that fail to be written are kept for the next batch,
+//! but counts that haven't been written yet are lost if the process stops, and on Lambda
+//! they are only written while the function handles requests, so the counts are a close
+//! estimate rather than an exact number.
+use chrono::{NaiveDate, Utc};
+use semver::Version;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::MissedTickBehavior;
+use tracing::error;
+
+use crate::models::download::DownloadCount;
+use crate::repository::DynRepository;
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+/// The number of distinct crate versions and days after which the counts are written
+/// without waiting for the interval.
+const MAX_PENDING_COUNTS: usize = 1000;
+
+type CountKey = (String, Version, NaiveDate);
+
+#[derive(Clone)]
+pub struct DownloadCounter {
+    sender: mpsc::UnboundedSender<CountKey>,
+}
+
+impl DownloadCounter {
+    /// Starts the task that writes the counts, so this has to be called within a Tokio runtime.
+    pub fn start(repository: DynRepository) -> Self {
+        Self::with_flush_interval(repository, FLUSH_INTERVAL)
+    }
+
+    pub fn with_flush_interval(repository: DynRepository, flush_interval: Duration) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        tokio::spawn(run(repository, receiver, flush_interval));
+
+        Self { sender }
+    }
+
+    pub fn record(&self, crate_name: &str, version: &Version) {
+        let key = (
+            crate_name.to_string(),
+            version.clone(),
+            Utc::now().date_naive(),
+        );
+        // the task only stops once every counter is dropped, so this can't fail
+        let _ = self.sender.send(key);
+    }
+}
+
+async fn run(
+    repository: DynRepository,
+    mut receiver: mpsc::UnboundedReceiver<CountKey>,
+    flush_interval: Duration,
+) {
+    let mut pending = HashMap::new();
+    // after a failed write, the counts wait for the interval instead of being retried
+    // with every download
+    let mut failing = false;
+    let mut interval = tokio::time::interval(flush_interval);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            key = receiver.recv() => match key {
+                Some(key) => {
+                    *pending.entry(key).or_default() += 1;
+                    if pending.len() >= MAX_PENDING_COUNTS && !failing {
+                        failing = !flush(&repository, &mut pending).await;
+                    }
+                }
+                None => {
+                    flush(&repository, &mut pending).await;
+                    return;
+                }
+            },
+            _ = interval.tick() => failing = !flush(&repository, &mut pending).await,
+        }
+    }
+}
+
+/// Writes the pending counts, and keeps the ones that could not be written.
+/// Returns whether every count was written.
+async fn flush(repository: &DynRepository, pending: &mut HashMap<CountKey, u64>) -> bool {
+    if pending.is_empty() {
+        return true;
+    }
+
+    let counts = pending
+        .drain()
+        .map(|((crate_name, version, date), downloads)| DownloadCount {
+            crate_name,
+            version,
+            date,
+            downloads,
+        })
+        .collect();
+    let unwritten = repository.add_downloads(counts).await;
+    if unwritten.is_empty() {
+        return true;
+    }
+
+    let unwritten_count = unwritten.len();
+    error!(
+        unwritten_count,
+        "failed to store download counts, retrying them later"
+    );
+    for count in unwritten {
+        *pending
+            .entry((count.crate_name, count.version, count.date))
+            .or_default() += count.downloads;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{InMemoryRepository, SqlRepository};
+    use sqlx::any::{install_default_drivers, AnyPoolOptions};
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_downloads_are_written_in_batches() {
+        let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+        let counter =
+            DownloadCounter::with_flush_interval(repository.clone(), Duration::from_millis(10));
+
+        let version = Version::new(0, 1, 0);
+        counter.record("foo", &version);
+        counter.record("foo", &version);
+        counter.record("foo", &Version::new(0, 2, 0));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let mut counts = repository.get_download_counts("foo").await.unwrap();
+        counts.sort_by(|a, b| a.version.cmp(&b.version));
+        let downloads: Vec<_> = counts
+            .into_iter()
+            .map(|count| (count.version.to_string(), count.downloads))
+            .collect();
+        assert_eq!(
+            downloads,
+            vec![("0.1.0".to_string(), 2), ("0.2.0".to_string(), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counts_that_fail_to_be_written_are_kept() {
+        // the database starts out without tables, so writing the counts fails
+        install_default_drivers();
+        let path = std::env::temp_dir().join(format!("raktar-{}.db", Uuid::new_v4()));
+        let database_url = format!("sqlite://{}?mode=rwc", path.display());
+        let pool = AnyPoolOptions::new().connect(&database_url).await.unwrap();
+        let repository = Arc::new(SqlRepository::new(pool.clone())) as DynRepository;
+        let counter =
+            DownloadCounter::with_flush_interval(repository.clone(), Duration::from_millis(10));
+
+        let version = Version::new(0, 1, 0);
+        counter.record("foo", &version);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        sqlx::migrate!().run(&pool).await.unwrap();
+        counter.record("foo", &version);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let counts = repository.get_download_counts("foo").await.unwrap();
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0].downloads, 2);
+    }
+}
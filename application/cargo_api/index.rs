@@ -1,32 +1,263 @@
-use axum::extract::{Path, State};
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use hex::ToHex;
+use http::header::{ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use http::{HeaderMap, HeaderValue, StatusCode, Uri};
+use sha2::{Digest, Sha256};
 
-use crate::error::AppResult;
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::index_prefix;
+use crate::models::index::IndexRevision;
+use crate::repository::DynRepository;
 use crate::router::AppState;
 
-pub async fn get_info_for_short_name_crate(
-    Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(1, crate_name.len());
+/// Serves the index file of a crate, for every route of the index layout.
+pub async fn get_index_file_handler(
+    uri: Uri,
+    State(AppState { repository, .. }): State<AppState>,
+    headers: HeaderMap,
+) -> AppResult<Response> {
+    let file_name = resolve_index_path(uri.path())
+        .ok_or_else(|| AppError::NonExistentPackageInfo(uri.path().to_string()))?;
+    let crate_name = resolve_crate_name(&repository, file_name).await?;
 
-    repository.get_package_info(&crate_name).await
+    get_index_file(&repository, &crate_name, &headers).await
 }
 
-pub async fn get_info_for_three_letter_crate(
-    Path((first_letter, crate_name)): Path<(String, String)>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_letter.as_ref()), crate_name.get(0..1));
+/// Validates the path of an index file and returns the file name, which is the crate name.
+///
+/// The directories have to follow the layout described in
+/// https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files,
+/// either in the original case of the crate name or in lowercase.
+fn resolve_index_path(path: &str) -> Option<&str> {
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    let (file_name, directories) = segments.split_last()?;
+    let is_valid_name = !file_name.is_empty()
+        && file_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !is_valid_name {
+        return None;
+    }
 
-    repository.get_package_info(&crate_name).await
+    let expected_prefix = index_prefix(file_name);
+    if directories.join("/").eq_ignore_ascii_case(&expected_prefix) {
+        Some(file_name)
+    } else {
+        None
+    }
 }
 
-pub async fn get_info_for_long_name_crate(
-    Path((first_two, second_two, crate_name)): Path<(String, String, String)>,
-    State((repository, _)): State<AppState>,
-) -> AppResult<String> {
-    assert_eq!(Some(first_two.as_ref()), crate_name.get(0..2));
-    assert_eq!(Some(second_two.as_ref()), crate_name.get(2..4));
+/// Finds the name the crate was published under.
+///
+/// Cargo requests index files with lowercase names, while crates can be published
+/// with uppercase letters in their names.
+async fn resolve_crate_name(repository: &DynRepository, file_name: &str) -> AppResult<String> {
+    match repository.lookup_crate_name(file_name).await? {
+        Some(crate_name) if crate_name.eq_ignore_ascii_case(file_name) => Ok(crate_name),
+        // the names only match after `-`/`_` normalization, so it's a different file
+        Some(_) => Err(AppError::NonExistentPackageInfo(file_name.to_string())),
+        // crates published before canonical names were tracked can only be found as they are
+        None => Ok(file_name.to_string()),
+    }
+}
+
+/// Responds with the index file of the crate, or with `304 Not Modified`
+/// if the client's cached copy is still up to date.
+///
+/// The revision of the index file is checked first, so that unchanged files
+/// can be answered without reading every version of the crate.
+async fn get_index_file(
+    repository: &DynRepository,
+    crate_name: &str,
+    headers: &HeaderMap,
+) -> AppResult<Response> {
+    let revision = repository.get_index_revision(crate_name).await?;
+    if let Some(revision) = &revision {
+        let validators = Validators::from_revision(revision);
+        if validators.is_not_modified(headers) {
+            return Ok(validators.respond(StatusCode::NOT_MODIFIED, ""));
+        }
+    }
+
+    let index_file = repository.get_package_info(crate_name).await?;
+    // crates published before revisions were tracked fall back to hashing the content
+    let validators = match &revision {
+        Some(revision) => Validators::from_revision(revision),
+        None => Validators::from_content(&index_file),
+    };
+    if validators.is_not_modified(headers) {
+        return Ok(validators.respond(StatusCode::NOT_MODIFIED, ""));
+    }
+
+    Ok(validators.respond(StatusCode::OK, index_file))
+}
+
+/// The values a client can use to check whether its cached index file is up to date.
+struct Validators {
+    etag: String,
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    fn from_revision(revision: &IndexRevision) -> Self {
+        Self {
+            etag: format!("\"{}\"", revision.revision),
+            last_modified: Some(revision.last_modified),
+        }
+    }
+
+    fn from_content(content: &str) -> Self {
+        let hash: String = Sha256::digest(content.as_bytes()).encode_hex();
+        Self {
+            etag: format!("\"{}\"", hash),
+            last_modified: None,
+        }
+    }
+
+    /// Evaluates the conditional request headers, as described in RFC 9110.
+    ///
+    /// `If-Modified-Since` is only considered when there is no `If-None-Match`.
+    fn is_not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(if_none_match) = headers.get(IF_NONE_MATCH) {
+            return if_none_match.to_str().is_ok_and(|value| {
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag)
+            });
+        }
+
+        match (self.last_modified, headers.get(IF_MODIFIED_SINCE)) {
+            (Some(last_modified), Some(if_modified_since)) => if_modified_since
+                .to_str()
+                .ok()
+                .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+                .is_some_and(|since| last_modified.timestamp() <= since.timestamp()),
+            _ => false,
+        }
+    }
+
+    fn respond(&self, status: StatusCode, body: impl IntoResponse) -> Response {
+        let mut response = (status, body).into_response();
+        let headers = response.headers_mut();
+        if let Ok(etag) = HeaderValue::from_str(&self.etag) {
+            headers.insert(ETAG, etag);
+        }
+        if let Some(last_modified) = self.last_modified {
+            let http_date = last_modified
+                .format("%a, %d %b %Y %H:%M:%S GMT")
+                .to_string();
+            if let Ok(value) = HeaderValue::from_str(&http_date) {
+                headers.insert(LAST_MODIFIED, value);
+            }
+        }
+
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn revision() -> IndexRevision {
+        IndexRevision {
+            revision: 3,
+            last_modified: Utc.with_ymd_and_hms(2023, 5, 1, 12, 0, 0).unwrap(),
+        }
+    }
+
+    fn headers(name: http::header::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_resolve_valid_index_paths() {
+        assert_eq!(resolve_index_path("/1/a"), Some("a"));
+        assert_eq!(resolve_index_path("/2/ab"), Some("ab"));
+        assert_eq!(resolve_index_path("/3/a/abc"), Some("abc"));
+        assert_eq!(resolve_index_path("/se/rd/serde"), Some("serde"));
+        assert_eq!(resolve_index_path("/se/rd/Serde"), Some("Serde"));
+        assert_eq!(resolve_index_path("/Se/rd/Serde"), Some("Serde"));
+        assert_eq!(resolve_index_path("/fo/o-/foo-bar"), Some("foo-bar"));
+    }
+
+    #[test]
+    fn test_resolve_invalid_index_paths() {
+        for path in [
+            "/ab/cd/xyz",
+            "/2/a",
+            "/1/ab",
+            "/3/b/abc",
+            "/ab/abc",
+            "/se/rd/ser",
+            "/se/rd/",
+            "/se/rd/serde.json",
+            "/3/%41/abc",
+            "/ab/cd/ef/abcd",
+        ] {
+            assert_eq!(resolve_index_path(path), None, "{} should be invalid", path);
+        }
+    }
+
+    #[test]
+    fn test_matching_etag_is_not_modified() {
+        let validators = Validators::from_revision(&revision());
+
+        assert!(validators.is_not_modified(&headers(IF_NONE_MATCH, "\"3\"")));
+        assert!(validators.is_not_modified(&headers(IF_NONE_MATCH, "\"1\", W/\"3\"")));
+        assert!(!validators.is_not_modified(&headers(IF_NONE_MATCH, "\"2\"")));
+        assert!(!validators.is_not_modified(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_if_modified_since() {
+        let validators = Validators::from_revision(&revision());
+
+        let same_time = headers(IF_MODIFIED_SINCE, "Mon, 01 May 2023 12:00:00 GMT");
+        assert!(validators.is_not_modified(&same_time));
+
+        let earlier = headers(IF_MODIFIED_SINCE, "Mon, 01 May 2023 11:59:59 GMT");
+        assert!(!validators.is_not_modified(&earlier));
+    }
+
+    #[test]
+    fn test_etag_takes_precedence_over_if_modified_since() {
+        let validators = Validators::from_revision(&revision());
+        let mut headers = headers(IF_MODIFIED_SINCE, "Mon, 01 May 2023 12:00:00 GMT");
+        headers.insert(IF_NONE_MATCH, HeaderValue::from_static("\"2\""));
+
+        assert!(!validators.is_not_modified(&headers));
+    }
+
+    #[test]
+    fn test_content_etag() {
+        let validators = Validators::from_content("{}");
+
+        let response = validators.respond(StatusCode::OK, "{}");
+
+        assert_eq!(
+            response.headers().get(ETAG).unwrap(),
+            "\"44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a\""
+        );
+        assert!(response.headers().get(LAST_MODIFIED).is_none());
+    }
+
+    #[test]
+    fn test_revision_headers() {
+        let validators = Validators::from_revision(&revision());
+
+        let response = validators.respond(StatusCode::NOT_MODIFIED, "");
 
-    repository.get_package_info(&crate_name).await
+        assert_eq!(response.headers().get(ETAG).unwrap(), "\"3\"");
+        assert_eq!(
+            response.headers().get(LAST_MODIFIED).unwrap(),
+            "Mon, 01 May 2023 12:00:00 GMT"
+        );
+    }
 }
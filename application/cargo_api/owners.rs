@@ -1,7 +1,8 @@
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
 use serde::{Deserialize, Serialize};
 
+use crate::auth::{AuthenticatedUser, EndpointScope};
 use crate::error::AppResult;
 use crate::models::user::User;
 use crate::router::AppState;
@@ -13,7 +14,7 @@ pub struct ListOwnersResponse {
 
 pub async fn list_owners(
     Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
+    State(AppState { repository, .. }): State<AppState>,
 ) -> AppResult<Json<ListOwnersResponse>> {
     let users = repository.list_owners(&crate_name).await?;
     let response = ListOwnersResponse { users };
@@ -34,9 +35,13 @@ pub struct AddOwnersResponse {
 
 pub async fn add_owners(
     Path(crate_name): Path<String>,
-    State((repository, _)): State<AppState>,
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    State(AppState { repository, .. }): State<AppState>,
     Json(new_owners): Json<AddOwnersBody>,
 ) -> AppResult<Json<AddOwnersResponse>> {
+    authenticated_user
+        .scopes
+        .check(EndpointScope::ChangeOwners, &crate_name)?;
     repository.add_owners(&crate_name, new_owners.users).await?;
 
     let response = AddOwnersResponse {
@@ -1,21 +1,26 @@
+mod archive;
+mod payload;
+
 use axum::body::Bytes;
 use axum::extract::State;
 use axum::{Extension, Json};
-use byteorder::{LittleEndian, ReadBytesExt};
 use hex::ToHex;
+use semver::Version;
 use serde::Serialize;
 use sha2::{Digest, Sha256};
-use std::io::{Cursor, Read};
-use tracing::info;
+use tracing::{error, info};
 
-use crate::auth::AuthenticatedUser;
-use crate::error::AppResult;
+use crate::auth::{AuthenticatedUser, EndpointScope};
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::{is_same_version, validate_crate_name};
 use crate::models::index::PackageInfo;
-use crate::models::metadata::Metadata;
 use crate::repository::DynRepository;
 use crate::router::AppState;
 use crate::storage::DynCrateStorage;
 
+pub use archive::verify_crate_archive;
+pub use payload::{PublishPayload, MAX_PUBLISH_BODY_SIZE};
+
 #[derive(Serialize)]
 pub struct PublishResponse {
     invalid_categories: Vec<String>,
@@ -25,7 +30,11 @@ pub struct PublishResponse {
 
 pub async fn publish_crate_handler(
     Extension(authenticated_user): Extension<AuthenticatedUser>,
-    State((repository, storage)): State<AppState>,
+    State(AppState {
+        repository,
+        storage,
+        ..
+    }): State<AppState>,
     body: Bytes,
 ) -> AppResult<Json<PublishResponse>> {
     publish_crate(authenticated_user, storage, repository, body).await?;
@@ -43,13 +52,24 @@ pub async fn publish_crate(
     repository: DynRepository,
     data: Bytes,
 ) -> AppResult<()> {
-    let (metadata_bytes, crate_bytes) = read_body(data);
-    let metadata = serde_json::from_slice::<Metadata>(&metadata_bytes).unwrap();
+    let PublishPayload {
+        metadata,
+        crate_bytes,
+    } = PublishPayload::parse(data)?;
 
-    info!("metadata: {}", serde_json::to_string(&metadata).unwrap());
+    info!("metadata: {}", serde_json::to_string(&metadata)?);
     let vers = metadata.vers.clone();
     let crate_name = metadata.name.clone();
+    validate_crate_name(&crate_name)?;
+    check_scope(&repository, &authenticated_user, &crate_name).await?;
+    check_for_conflicts(&repository, &crate_name, &vers).await?;
+    check_ownership(&repository, &authenticated_user, &crate_name).await?;
+    verify_crate_archive(&crate_bytes, &metadata)?;
+
     let checksum: String = Sha256::digest(&crate_bytes).encode_hex();
+    if let Some(claims) = &authenticated_user.signed_claims {
+        claims.check_publish(&crate_name, &vers, &checksum)?;
+    }
     let package_info = PackageInfo::from_metadata(metadata.clone(), &checksum);
 
     info!(
@@ -61,7 +81,15 @@ pub async fn publish_crate(
         vers
     );
 
-    repository
+    // The crate is uploaded before the index is updated, so that a version
+    // never shows up in the index without a crate that can be downloaded.
+    // Storage refuses to overwrite a crate, so when two publishes of the same
+    // version race, only the first upload claims it and the other one fails here.
+    storage
+        .store_crate(&crate_name, vers.clone(), crate_bytes)
+        .await?;
+
+    let result = repository
         .store_package_info(
             &crate_name,
             &vers,
@@ -69,24 +97,81 @@ pub async fn publish_crate(
             metadata,
             &authenticated_user,
         )
-        .await?;
-    storage.store_crate(&crate_name, vers, crate_bytes).await?;
+        .await;
+
+    // Storage refused to overwrite any existing crate, so the stored crate is ours even when
+    // the index already has the version, whose crate must have been missing then. The crate
+    // probably doesn't match the checksum of that index entry, so it's deleted either way.
+    if let Err(err) = result {
+        if let Err(delete_err) = storage.delete_crate(&crate_name, vers.clone()).await {
+            error!(
+                crate_name,
+                vers = vers.to_string(),
+                error_message = delete_err.to_string(),
+                "failed to clean up crate after failed publish"
+            );
+        }
+        return Err(err);
+    }
 
     Ok(())
 }
 
-fn read_body(body: Bytes) -> (Vec<u8>, Vec<u8>) {
-    let mut cursor = Cursor::new(body);
+/// Makes sure the token is allowed to publish the first version of a new crate,
+/// or a new version of an existing one.
+async fn check_scope(
+    repository: &DynRepository,
+    authenticated_user: &AuthenticatedUser,
+    crate_name: &str,
+) -> AppResult<()> {
+    let scope = match repository.lookup_crate_name(crate_name).await? {
+        Some(_) => EndpointScope::PublishUpdate,
+        None => EndpointScope::PublishNew,
+    };
 
-    // read metadata bytes
-    let metadata_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut metadata_bytes = vec![0u8; metadata_length as usize];
-    cursor.read_exact(&mut metadata_bytes).unwrap();
+    authenticated_user.scopes.check(scope, crate_name)
+}
 
-    // read crate bytes
-    let crate_length = cursor.read_u32::<LittleEndian>().unwrap();
-    let mut crate_bytes = vec![0u8; crate_length as usize];
-    cursor.read_exact(&mut crate_bytes).unwrap();
+/// Makes sure only the owners of an existing crate can publish new versions of it,
+/// before anything is uploaded.
+async fn check_ownership(
+    repository: &DynRepository,
+    authenticated_user: &AuthenticatedUser,
+    crate_name: &str,
+) -> AppResult<()> {
+    if let Some(summary) = repository.get_crate_summary(crate_name).await? {
+        if !summary.owners.contains(&authenticated_user.id) {
+            return Err(AppError::Unauthorized(
+                "user is not an owner of this package".to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
 
-    (metadata_bytes, crate_bytes)
+/// Makes sure the new version doesn't clash with an already published crate or version.
+async fn check_for_conflicts(
+    repository: &DynRepository,
+    crate_name: &str,
+    vers: &Version,
+) -> AppResult<()> {
+    if let Some(existing_name) = repository.lookup_crate_name(crate_name).await? {
+        if existing_name != crate_name {
+            return Err(AppError::CrateNameConflict {
+                crate_name: crate_name.to_string(),
+                existing_name,
+            });
+        }
+    }
+
+    let existing_versions = repository.list_crate_versions(crate_name).await?;
+    if existing_versions.iter().any(|v| is_same_version(v, vers)) {
+        return Err(AppError::DuplicateCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: vers.clone(),
+        });
+    }
+
+    Ok(())
 }
@@ -0,0 +1,277 @@
+//! Verification of the `.crate` archive uploaded by `cargo publish`.
+//!
+//! The archive is a gzipped tarball that Cargo builds with `cargo package`.
+//! Every entry lives under a single `{name}-{version}/` directory, and it
+//! contains the normalised `Cargo.toml` of the package.
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::{Component, Path};
+use std::str::FromStr;
+use tar::{Archive, EntryType};
+
+use semver::Version;
+
+use crate::error::{AppError, AppResult};
+use crate::models::metadata::Metadata;
+
+/// The maximum total size of the files in the archive once it's unpacked.
+pub const MAX_UNPACKED_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    package: ManifestPackage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestPackage {
+    name: String,
+    version: String,
+}
+
+/// Checks that the archive is well formed and that it matches the metadata.
+pub fn verify_crate_archive(crate_bytes: &[u8], metadata: &Metadata) -> AppResult<()> {
+    let root = format!("{}-{}", metadata.name, metadata.vers);
+    // the limit on the decoder guards against archives made up of a huge number of
+    // headers, the entry sizes are checked below to give a more useful error message
+    let decoder = GzDecoder::new(crate_bytes).take(2 * MAX_UNPACKED_SIZE);
+    let mut archive = Archive::new(decoder);
+
+    let mut manifest = None;
+    let mut unpacked_size: u64 = 0;
+    let entries = archive
+        .entries()
+        .map_err(|_| invalid("the archive is not a gzipped tarball"))?;
+    for entry in entries {
+        let mut entry = entry.map_err(|_| invalid("the archive could not be read"))?;
+
+        let entry_size = entry
+            .header()
+            .size()
+            .map_err(|_| invalid("an entry in the archive has an invalid size"))?;
+        unpacked_size = unpacked_size.saturating_add(entry_size);
+        if unpacked_size > MAX_UNPACKED_SIZE {
+            return Err(invalid(&format!(
+                "the unpacked archive is larger than the maximum of {} bytes",
+                MAX_UNPACKED_SIZE
+            )));
+        }
+
+        let entry_type = entry.header().entry_type();
+        if matches!(entry_type, EntryType::Link | EntryType::Symlink) {
+            return Err(invalid("the archive cannot contain links"));
+        }
+
+        let path = entry
+            .path()
+            .map_err(|_| invalid("the archive contains an invalid path"))?
+            .into_owned();
+        verify_entry_path(&path, &root)?;
+
+        if path == Path::new(&root).join("Cargo.toml") {
+            let mut contents = String::new();
+            entry
+                .read_to_string(&mut contents)
+                .map_err(|_| invalid("Cargo.toml could not be read"))?;
+            manifest = Some(contents);
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| invalid(&format!("{}/Cargo.toml is missing", root)))?;
+    verify_manifest(&manifest, metadata)
+}
+
+fn verify_entry_path(path: &Path, root: &str) -> AppResult<()> {
+    let mut components = path.components();
+    if components.next() != Some(Component::Normal(root.as_ref())) {
+        return Err(invalid(&format!(
+            "the entry {} is not in the {}/ directory",
+            path.display(),
+            root
+        )));
+    }
+    if !components.all(|c| matches!(c, Component::Normal(_))) {
+        return Err(invalid(&format!(
+            "the entry {} has an invalid path",
+            path.display()
+        )));
+    }
+
+    Ok(())
+}
+
+fn verify_manifest(manifest: &str, metadata: &Metadata) -> AppResult<()> {
+    let manifest = toml::from_str::<Manifest>(manifest)
+        .map_err(|_| invalid("Cargo.toml does not contain a valid package"))?;
+
+    if manifest.package.name != metadata.name {
+        return Err(invalid(&format!(
+            "the package name in Cargo.toml is {}, but {} was published",
+            manifest.package.name, metadata.name
+        )));
+    }
+    if Version::from_str(&manifest.package.version).ok().as_ref() != Some(&metadata.vers) {
+        return Err(invalid(&format!(
+            "the package version in Cargo.toml is {}, but {} was published",
+            manifest.package.version, metadata.vers
+        )));
+    }
+
+    Ok(())
+}
+
+fn invalid(reason: &str) -> AppError {
+    AppError::InvalidCrateArchive(reason.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tar::{Builder, Header};
+
+    const MANIFEST: &str = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+
+    fn build_metadata(name: &str, vers: &str) -> Metadata {
+        let json = format!(
+            r#"{{"name":"{}","vers":"{}","deps":[],"features":{{}},"authors":[],"description":null,"documentation":null,"homepage":null,"readme":null,"readme_file":null,"keywords":[],"categories":[],"license":null,"license_file":null,"repository":null,"badges":{{}},"links":null}}"#,
+            name, vers
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn build_archive(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = GzEncoder::new(vec![], Compression::default());
+        let mut builder = Builder::new(encoder);
+        for (path, data) in entries {
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            // `set_path` refuses paths with `..`, so the name is written directly
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn verify_err(archive: &[u8], metadata: &Metadata) -> String {
+        match verify_crate_archive(archive, metadata) {
+            Err(AppError::InvalidCrateArchive(reason)) => reason,
+            other => panic!("expected an invalid archive error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_valid_archive() {
+        let archive = build_archive(&[
+            ("foo-0.1.0/Cargo.toml", MANIFEST.as_bytes()),
+            ("foo-0.1.0/src/lib.rs", b""),
+        ]);
+
+        verify_crate_archive(&archive, &build_metadata("foo", "0.1.0")).unwrap();
+    }
+
+    #[test]
+    fn test_not_a_tarball() {
+        let reason = verify_err(b"not a tarball", &build_metadata("foo", "0.1.0"));
+
+        assert_eq!(reason, "the archive could not be read");
+    }
+
+    #[test]
+    fn test_missing_manifest() {
+        let archive = build_archive(&[("foo-0.1.0/src/lib.rs", b"")]);
+
+        let reason = verify_err(&archive, &build_metadata("foo", "0.1.0"));
+
+        assert_eq!(reason, "foo-0.1.0/Cargo.toml is missing");
+    }
+
+    #[test]
+    fn test_wrong_root_directory() {
+        let archive = build_archive(&[
+            ("foo-0.1.0/Cargo.toml", MANIFEST.as_bytes()),
+            ("bar-0.1.0/src/lib.rs", b""),
+        ]);
+
+        let reason = verify_err(&archive, &build_metadata("foo", "0.1.0"));
+
+        assert_eq!(
+            reason,
+            "the entry bar-0.1.0/src/lib.rs is not in the foo-0.1.0/ directory"
+        );
+    }
+
+    #[test]
+    fn test_path_traversal() {
+        let archive = build_archive(&[
+            ("foo-0.1.0/Cargo.toml", MANIFEST.as_bytes()),
+            ("foo-0.1.0/../../etc/passwd", b""),
+        ]);
+
+        let reason = verify_err(&archive, &build_metadata("foo", "0.1.0"));
+
+        assert_eq!(
+            reason,
+            "the entry foo-0.1.0/../../etc/passwd has an invalid path"
+        );
+    }
+
+    #[test]
+    fn test_mismatched_name() {
+        let archive = build_archive(&[("bar-0.1.0/Cargo.toml", MANIFEST.as_bytes())]);
+
+        let reason = verify_err(&archive, &build_metadata("bar", "0.1.0"));
+
+        assert_eq!(
+            reason,
+            "the package name in Cargo.toml is foo, but bar was published"
+        );
+    }
+
+    #[test]
+    fn test_mismatched_version() {
+        let archive = build_archive(&[("foo-0.2.0/Cargo.toml", MANIFEST.as_bytes())]);
+
+        let reason = verify_err(&archive, &build_metadata("foo", "0.2.0"));
+
+        assert_eq!(
+            reason,
+            "the package version in Cargo.toml is 0.1.0, but 0.2.0 was published"
+        );
+    }
+
+    #[test]
+    fn test_invalid_entry_size() {
+        let mut header = Header::new_gnu();
+        header.set_path("foo-0.1.0/Cargo.toml").unwrap();
+        header.set_mode(0o644);
+        header.as_gnu_mut().unwrap().size = *b"not a size\0\0";
+        header.set_cksum();
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(header.as_bytes()).unwrap();
+        encoder.write_all(&[0; 1024]).unwrap();
+        let archive = encoder.finish().unwrap();
+
+        let result = verify_crate_archive(&archive, &build_metadata("foo", "0.1.0"));
+
+        assert!(matches!(result, Err(AppError::InvalidCrateArchive(_))));
+    }
+
+    #[test]
+    fn test_unpacked_size_limit() {
+        let large_file = vec![0u8; MAX_UNPACKED_SIZE as usize];
+        let archive = build_archive(&[
+            ("foo-0.1.0/Cargo.toml", MANIFEST.as_bytes()),
+            ("foo-0.1.0/large.bin", &large_file),
+        ]);
+
+        let reason = verify_err(&archive, &build_metadata("foo", "0.1.0"));
+
+        assert!(reason.starts_with("the unpacked archive is larger than the maximum"));
+    }
+}
@@ -0,0 +1,182 @@
+//! Parsing of the request body `cargo publish` sends.
+//!
+//! The body is described in the Cargo reference:
+//! https://doc.rust-lang.org/cargo/reference/registry-web-api.html#publish
+//!
+//! It consists of a 32-bit little-endian length, the JSON metadata, another
+//! 32-bit little-endian length and then the `.crate` archive itself.
+use axum::body::Bytes;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read};
+
+use crate::error::{AppError, AppResult};
+use crate::models::metadata::Metadata;
+
+/// The maximum size of the JSON metadata we accept.
+pub const MAX_METADATA_SIZE: usize = 1024 * 1024;
+/// The maximum size of the compressed `.crate` archive we accept.
+pub const MAX_CRATE_SIZE: usize = 10 * 1024 * 1024;
+/// The maximum size of the whole publish body, including the two length prefixes.
+pub const MAX_PUBLISH_BODY_SIZE: usize = MAX_METADATA_SIZE + MAX_CRATE_SIZE + 8;
+
+/// A publish request body that has been split into its two parts.
+#[derive(Debug)]
+pub struct PublishPayload {
+    pub metadata: Metadata,
+    pub crate_bytes: Vec<u8>,
+}
+
+impl PublishPayload {
+    pub fn parse(body: Bytes) -> AppResult<Self> {
+        if body.len() > MAX_PUBLISH_BODY_SIZE {
+            return Err(invalid(format!(
+                "the request body is larger than the maximum of {} bytes",
+                MAX_PUBLISH_BODY_SIZE
+            )));
+        }
+
+        let mut cursor = Cursor::new(body);
+        let metadata_bytes = read_section(&mut cursor, "metadata", MAX_METADATA_SIZE)?;
+        let crate_bytes = read_section(&mut cursor, "crate file", MAX_CRATE_SIZE)?;
+
+        let remaining = cursor.get_ref().len() as u64 - cursor.position();
+        if remaining > 0 {
+            return Err(invalid(format!(
+                "the request body has {} unexpected trailing bytes",
+                remaining
+            )));
+        }
+
+        let metadata = serde_json::from_slice::<Metadata>(&metadata_bytes)
+            .map_err(|err| invalid(format!("the metadata is not valid JSON: {}", err)))?;
+
+        Ok(Self {
+            metadata,
+            crate_bytes,
+        })
+    }
+}
+
+fn read_section(cursor: &mut Cursor<Bytes>, section: &str, max_size: usize) -> AppResult<Vec<u8>> {
+    let length = cursor
+        .read_u32::<LittleEndian>()
+        .map_err(|_| invalid(format!("the {} length is missing", section)))?
+        as usize;
+    if length > max_size {
+        return Err(invalid(format!(
+            "the {} is {} bytes, which is larger than the maximum of {} bytes",
+            section, length, max_size
+        )));
+    }
+
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if (length as u64) > remaining {
+        return Err(invalid(format!(
+            "the {} is declared as {} bytes, but only {} bytes were sent",
+            section, length, remaining
+        )));
+    }
+
+    let mut bytes = vec![0u8; length];
+    cursor
+        .read_exact(&mut bytes)
+        .map_err(|_| invalid(format!("failed to read the {}", section)))?;
+
+    Ok(bytes)
+}
+
+fn invalid(reason: String) -> AppError {
+    AppError::InvalidPublishPayload(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{"name":"foo","vers":"0.1.0","deps":[],"features":{},"authors":[],"description":null,"documentation":null,"homepage":null,"readme":null,"readme_file":null,"keywords":[],"categories":[],"license":null,"license_file":null,"repository":null,"badges":{},"links":null}"#;
+
+    fn build_body(metadata: &[u8], crate_bytes: &[u8]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+        body.extend_from_slice(metadata);
+        body.extend_from_slice(&(crate_bytes.len() as u32).to_le_bytes());
+        body.extend_from_slice(crate_bytes);
+        body
+    }
+
+    fn parse_err(body: Vec<u8>) -> String {
+        match PublishPayload::parse(Bytes::from(body)) {
+            Err(AppError::InvalidPublishPayload(reason)) => reason,
+            other => panic!("expected an invalid payload error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_valid_payload() {
+        let body = build_body(METADATA.as_bytes(), b"crate");
+
+        let payload = PublishPayload::parse(Bytes::from(body)).unwrap();
+
+        assert_eq!(payload.metadata.name, "foo");
+        assert_eq!(payload.crate_bytes, b"crate");
+    }
+
+    #[test]
+    fn test_empty_body_is_rejected() {
+        let reason = parse_err(vec![]);
+
+        assert_eq!(reason, "the metadata length is missing");
+    }
+
+    #[test]
+    fn test_truncated_metadata_is_rejected() {
+        let mut body = build_body(METADATA.as_bytes(), b"crate");
+        body.truncate(20);
+
+        let reason = parse_err(body);
+
+        assert!(reason.starts_with("the metadata is declared as"));
+    }
+
+    #[test]
+    fn test_truncated_crate_is_rejected() {
+        let mut body = build_body(METADATA.as_bytes(), b"crate");
+        body.pop();
+
+        let reason = parse_err(body);
+
+        assert_eq!(
+            reason,
+            "the crate file is declared as 5 bytes, but only 4 bytes were sent"
+        );
+    }
+
+    #[test]
+    fn test_trailing_bytes_are_rejected() {
+        let mut body = build_body(METADATA.as_bytes(), b"crate");
+        body.push(0);
+
+        let reason = parse_err(body);
+
+        assert_eq!(reason, "the request body has 1 unexpected trailing bytes");
+    }
+
+    #[test]
+    fn test_oversized_metadata_is_rejected() {
+        let mut body = vec![];
+        body.extend_from_slice(&(MAX_METADATA_SIZE as u32 + 1).to_le_bytes());
+
+        let reason = parse_err(body);
+
+        assert!(reason.starts_with("the metadata is 1048577 bytes"));
+    }
+
+    #[test]
+    fn test_invalid_json_is_rejected() {
+        let body = build_body(b"{\"name\": ", b"crate");
+
+        let reason = parse_err(body);
+
+        assert!(reason.starts_with("the metadata is not valid JSON"));
+    }
+}
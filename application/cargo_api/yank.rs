@@ -1,11 +1,12 @@
 use std::str::FromStr;
 
 use axum::extract::{Path, State};
-use axum::Json;
+use axum::{Extension, Json};
 use semver::Version;
 use serde::Serialize;
 
-use crate::error::AppResult;
+use crate::auth::{AuthenticatedUser, EndpointScope};
+use crate::error::{AppError, AppResult};
 use crate::router::AppState;
 
 #[derive(Serialize)]
@@ -15,9 +16,13 @@ pub struct Response {
 
 pub async fn yank(
     Path((crate_name, version)): Path<(String, String)>,
-    State((repository, _)): State<AppState>,
+    Extension(authenticated_user): Extension<AuthenticatedUser>,
+    State(AppState { repository, .. }): State<AppState>,
 ) -> AppResult<Json<Response>> {
-    let vers = Version::from_str(&version).expect("version to be valid");
+    authenticated_user
+        .scopes
+        .check(EndpointScope::Yank, &crate_name)?;
+    let vers = Version::from_str(&version).map_err(|_| AppError::InvalidVersion(version))?;
     repository.set_yanked(&crate_name, &vers, true).await?;
 
     let response = Json(Response { ok: true });
@@ -0,0 +1,415 @@
+//! Configuration of the registry, loaded once when the application starts.
+use anyhow::{anyhow, bail};
+use std::str::FromStr;
+
+use crate::auth::jwt::{JwksSource, JwtConfig};
+use crate::auth::oidc::OidcConfig;
+use crate::auth::CratePattern;
+
+/// The markers Cargo replaces in the download URL template, as described in
+/// https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration
+const DOWNLOAD_URL_MARKERS: &[&str] = &[
+    "crate",
+    "version",
+    "prefix",
+    "lowerprefix",
+    "sha256-checksum",
+];
+/// The shortest secret sessions can be signed with.
+const MIN_SESSION_SECRET_LENGTH: usize = 32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegistryConfig {
+    /// The `dl` field of `config.json`, which may contain markers such as `{crate}`.
+    pub download_url_template: String,
+    /// The `api` field of `config.json`, the base URL of the web API.
+    pub api_url: String,
+    /// The URL Cargo is configured with for the index, which Cargo signs asymmetric tokens for.
+    pub index_url: String,
+    /// Whether Cargo has to send a token for every request, including index requests.
+    pub auth_required: bool,
+    /// Which crates can be read from the index and downloaded without a token.
+    ///
+    /// Cargo only reads anonymously if `auth_required` is turned off as well.
+    pub anonymous_read: AnonymousRead,
+    /// How the tokens of the web frontend are verified, without it the GraphQL API
+    /// turns every request away.
+    pub jwt: Option<JwtConfig>,
+    /// The provider users log in to the web frontend with, when it's not Cognito.
+    pub oidc: Option<OidcConfig>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnonymousRead {
+    Disabled,
+    AllCrates,
+    Crates(Vec<CratePattern>),
+}
+
+impl AnonymousRead {
+    pub fn allows(&self, crate_name: &str) -> bool {
+        match self {
+            AnonymousRead::Disabled => false,
+            AnonymousRead::AllCrates => true,
+            AnonymousRead::Crates(patterns) => patterns.iter().any(|p| p.matches(crate_name)),
+        }
+    }
+}
+
+impl RegistryConfig {
+    /// The default configuration for a registry served from the given domain.
+    pub fn for_domain(domain_name: &str) -> Self {
+        Self {
+            download_url_template: format!("https://{}/api/v1/crates", domain_name),
+            api_url: format!("https://{}", domain_name),
+            index_url: format!("sparse+https://{}/", domain_name),
+            auth_required: true,
+            anonymous_read: AnonymousRead::Disabled,
+            jwt: None,
+            oidc: None,
+        }
+    }
+
+    pub fn from_env() -> anyhow::Result<Self> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    /// Builds the configuration from variables looked up with `get_var`.
+    ///
+    /// `DOMAIN_NAME` sets the defaults, which can be overridden with `DOWNLOAD_URL_TEMPLATE`,
+    /// `API_URL`, `INDEX_URL`, `AUTH_REQUIRED` and `ANONYMOUS_READ`.
+    /// Instead of opening up every crate with `ANONYMOUS_READ`, `ANONYMOUS_READ_CRATES`
+    /// can list comma-separated crate patterns, such as `public-*,serde-utils`.
+    ///
+    /// The tokens of the web frontend are verified when both `JWT_ISSUER` and `JWT_AUDIENCE`
+    /// are set. The keys are loaded from the issuer, unless `JWKS_FILE` points to a key set.
+    ///
+    /// Users log in with an OIDC provider when both `OIDC_ISSUER` and `OIDC_CLIENT_ID` are set,
+    /// along with `OIDC_CLIENT_SECRET` for confidential clients, and a `SESSION_SECRET` of at
+    /// least 32 characters. `OIDC_REDIRECT_URL` defaults to `/auth/callback` on the API, and
+    /// `FRONTEND_URL` to the API URL without its `api.` subdomain.
+    pub fn from_vars(get_var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        let domain_name = get_var("DOMAIN_NAME");
+        let default_config = domain_name.as_deref().map(Self::for_domain);
+        let missing_domain = || anyhow!("DOMAIN_NAME is not set in environment");
+
+        let download_url_template = match get_var("DOWNLOAD_URL_TEMPLATE") {
+            Some(template) => template,
+            None => default_config
+                .as_ref()
+                .ok_or_else(missing_domain)?
+                .download_url_template
+                .clone(),
+        };
+        let api_url = match get_var("API_URL") {
+            Some(url) => url,
+            None => default_config
+                .as_ref()
+                .ok_or_else(missing_domain)?
+                .api_url
+                .clone(),
+        };
+        let index_url = match get_var("INDEX_URL") {
+            Some(url) => url,
+            None => default_config
+                .as_ref()
+                .ok_or_else(missing_domain)?
+                .index_url
+                .clone(),
+        };
+        let auth_required = parse_flag(get_var("AUTH_REQUIRED"), "AUTH_REQUIRED", true)?;
+        let anonymous_read = if parse_flag(get_var("ANONYMOUS_READ"), "ANONYMOUS_READ", false)? {
+            AnonymousRead::AllCrates
+        } else if let Some(patterns) = get_var("ANONYMOUS_READ_CRATES") {
+            let patterns = patterns
+                .split(',')
+                .map(|p| CratePattern::from_str(p.trim()))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            AnonymousRead::Crates(patterns)
+        } else {
+            AnonymousRead::Disabled
+        };
+
+        let jwt = match (get_var("JWT_ISSUER"), get_var("JWT_AUDIENCE")) {
+            (Some(issuer), Some(audience)) => {
+                let jwks = match get_var("JWKS_FILE") {
+                    Some(path) => JwksSource::File(path.into()),
+                    None => JwksSource::for_issuer(&issuer),
+                };
+                Some(JwtConfig {
+                    issuer,
+                    audience,
+                    jwks,
+                })
+            }
+            (None, None) => None,
+            _ => bail!("JWT_ISSUER and JWT_AUDIENCE must be set together"),
+        };
+
+        let oidc = match (get_var("OIDC_ISSUER"), get_var("OIDC_CLIENT_ID")) {
+            (Some(issuer), Some(client_id)) => {
+                let session_secret = get_var("SESSION_SECRET")
+                    .ok_or_else(|| anyhow!("SESSION_SECRET must be set to log in with OIDC"))?;
+                if session_secret.len() < MIN_SESSION_SECRET_LENGTH {
+                    bail!(
+                        "SESSION_SECRET must be at least {} characters long",
+                        MIN_SESSION_SECRET_LENGTH
+                    );
+                }
+                let redirect_url = get_var("OIDC_REDIRECT_URL")
+                    .unwrap_or_else(|| format!("{}/auth/callback", api_url.trim_end_matches('/')));
+                let frontend_url = get_var("FRONTEND_URL")
+                    .unwrap_or_else(|| api_url.replacen("://api.", "://", 1));
+                Some(OidcConfig {
+                    issuer,
+                    client_id,
+                    client_secret: get_var("OIDC_CLIENT_SECRET"),
+                    redirect_url,
+                    frontend_url,
+                    session_secret,
+                })
+            }
+            (None, None) => None,
+            _ => bail!("OIDC_ISSUER and OIDC_CLIENT_ID must be set together"),
+        };
+
+        let config = Self {
+            download_url_template,
+            api_url,
+            index_url,
+            auth_required,
+            anonymous_read,
+            jwt,
+            oidc,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks that the download URL template only contains markers Cargo knows about.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut rest = self.download_url_template.as_str();
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("unclosed marker in the download URL template"))?;
+            let marker = &rest[start + 1..start + end];
+            if !DOWNLOAD_URL_MARKERS.contains(&marker) {
+                bail!("unknown marker {{{}}} in the download URL template", marker);
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_flag(value: Option<String>, name: &str, default: bool) -> anyhow::Result<bool> {
+    match value {
+        None => Ok(default),
+        Some(value) => bool::from_str(&value)
+            .map_err(|_| anyhow!("{} must be either true or false, got {}", name, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> anyhow::Result<RegistryConfig> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        RegistryConfig::from_vars(|key| vars.get(key).cloned())
+    }
+
+    #[test]
+    fn test_defaults_from_domain_name() {
+        let config = load(&[("DOMAIN_NAME", "raktar.io")]).unwrap();
+
+        assert_eq!(config, RegistryConfig::for_domain("raktar.io"));
+        assert_eq!(
+            config.download_url_template,
+            "https://raktar.io/api/v1/crates"
+        );
+        assert_eq!(config.api_url, "https://raktar.io");
+        assert_eq!(config.index_url, "sparse+https://raktar.io/");
+        assert!(config.auth_required);
+        assert_eq!(config.anonymous_read, AnonymousRead::Disabled);
+        assert_eq!(config.jwt, None);
+        assert_eq!(config.oidc, None);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let config = load(&[
+            (
+                "DOWNLOAD_URL_TEMPLATE",
+                "https://cdn.raktar.io/{lowerprefix}/{crate}",
+            ),
+            ("API_URL", "https://api.raktar.io"),
+            ("INDEX_URL", "sparse+https://index.raktar.io/"),
+            ("AUTH_REQUIRED", "false"),
+            ("ANONYMOUS_READ", "true"),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.download_url_template,
+            "https://cdn.raktar.io/{lowerprefix}/{crate}"
+        );
+        assert_eq!(config.api_url, "https://api.raktar.io");
+        assert_eq!(config.index_url, "sparse+https://index.raktar.io/");
+        assert!(!config.auth_required);
+        assert_eq!(config.anonymous_read, AnonymousRead::AllCrates);
+    }
+
+    #[test]
+    fn test_anonymous_read_for_some_crates() {
+        let config = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("ANONYMOUS_READ_CRATES", "public-*, serde-utils"),
+        ])
+        .unwrap();
+
+        assert!(config.anonymous_read.allows("public-api"));
+        assert!(config.anonymous_read.allows("serde_utils"));
+        assert!(!config.anonymous_read.allows("private-api"));
+    }
+
+    #[test]
+    fn test_jwt() {
+        let issuer = "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_abc";
+        let config = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("JWT_ISSUER", issuer),
+            ("JWT_AUDIENCE", "client"),
+        ])
+        .unwrap();
+
+        let jwt = config.jwt.unwrap();
+        assert_eq!(jwt.issuer, issuer);
+        assert_eq!(jwt.audience, "client");
+        assert_eq!(
+            jwt.jwks,
+            JwksSource::Url(format!("{}/.well-known/jwks.json", issuer))
+        );
+
+        let config = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("JWT_ISSUER", issuer),
+            ("JWT_AUDIENCE", "client"),
+            ("JWKS_FILE", "jwks.json"),
+        ])
+        .unwrap();
+        assert_eq!(
+            config.jwt.unwrap().jwks,
+            JwksSource::File("jwks.json".into())
+        );
+
+        let err = load(&[("DOMAIN_NAME", "raktar.io"), ("JWT_ISSUER", issuer)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "JWT_ISSUER and JWT_AUDIENCE must be set together"
+        );
+    }
+
+    #[test]
+    fn test_oidc() {
+        let secret = "a secret that is long enough to use";
+        let config = load(&[
+            ("API_URL", "https://api.raktar.io"),
+            (
+                "DOWNLOAD_URL_TEMPLATE",
+                "https://api.raktar.io/api/v1/crates",
+            ),
+            ("INDEX_URL", "sparse+https://api.raktar.io/"),
+            ("OIDC_ISSUER", "https://keycloak.raktar.io/realms/raktar"),
+            ("OIDC_CLIENT_ID", "raktar"),
+            ("SESSION_SECRET", secret),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            config.oidc.unwrap(),
+            OidcConfig {
+                issuer: "https://keycloak.raktar.io/realms/raktar".to_string(),
+                client_id: "raktar".to_string(),
+                client_secret: None,
+                redirect_url: "https://api.raktar.io/auth/callback".to_string(),
+                frontend_url: "https://raktar.io".to_string(),
+                session_secret: secret.to_string(),
+            }
+        );
+
+        let err = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("OIDC_ISSUER", "https://keycloak.raktar.io/realms/raktar"),
+            ("OIDC_CLIENT_ID", "raktar"),
+            ("SESSION_SECRET", "short"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "SESSION_SECRET must be at least 32 characters long"
+        );
+
+        let err = load(&[("DOMAIN_NAME", "raktar.io"), ("OIDC_CLIENT_ID", "raktar")]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "OIDC_ISSUER and OIDC_CLIENT_ID must be set together"
+        );
+    }
+
+    #[test]
+    fn test_debug_hides_secrets() {
+        let config = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("OIDC_ISSUER", "https://keycloak.raktar.io/realms/raktar"),
+            ("OIDC_CLIENT_ID", "raktar"),
+            ("OIDC_CLIENT_SECRET", "the client secret"),
+            ("SESSION_SECRET", "a secret that is long enough to use"),
+        ])
+        .unwrap();
+
+        let debug = format!("{:?}", config);
+
+        assert!(debug.contains("https://keycloak.raktar.io/realms/raktar"));
+        assert!(!debug.contains("the client secret"));
+        assert!(!debug.contains("a secret that is long enough to use"));
+    }
+
+    #[test]
+    fn test_missing_domain_name() {
+        let err = load(&[]).unwrap_err();
+
+        assert_eq!(err.to_string(), "DOMAIN_NAME is not set in environment");
+    }
+
+    #[test]
+    fn test_invalid_flag() {
+        let err = load(&[("DOMAIN_NAME", "raktar.io"), ("AUTH_REQUIRED", "yes")]).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "AUTH_REQUIRED must be either true or false, got yes"
+        );
+    }
+
+    #[test]
+    fn test_unknown_marker() {
+        let err = load(&[
+            ("DOMAIN_NAME", "raktar.io"),
+            ("DOWNLOAD_URL_TEMPLATE", "https://raktar.io/{name}"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "unknown marker {name} in the download URL template"
+        );
+    }
+}
@@ -27,6 +27,21 @@ pub enum AppError {
     },
     #[error("package info for {0} does not exist")]
     Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("invalid publish request: {0}")]
+    InvalidPublishPayload(String),
+    #[error("{0}")]
+    InvalidCrateName(String),
+    #[error("invalid version {0}")]
+    InvalidVersion(String),
+    #[error("invalid crate archive: {0}")]
+    InvalidCrateArchive(String),
+    #[error("crate name {crate_name} is too similar to the existing crate {existing_name}")]
+    CrateNameConflict {
+        crate_name: String,
+        existing_name: String,
+    },
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
     #[error("unexpected error")]
@@ -42,6 +57,12 @@ impl IntoResponse for AppError {
             AppError::NonExistentCrateVersion { .. } => StatusCode::NOT_FOUND,
             AppError::DuplicateCrateVersion { .. } => StatusCode::BAD_REQUEST,
             AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::InvalidPublishPayload(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidCrateName(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidVersion(_) => StatusCode::BAD_REQUEST,
+            AppError::InvalidCrateArchive(_) => StatusCode::BAD_REQUEST,
+            AppError::CrateNameConflict { .. } => StatusCode::BAD_REQUEST,
             AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
             AppError::Other(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
@@ -89,6 +110,15 @@ impl From<ParseIntError> for AppError {
     }
 }
 
+impl From<sqlx::Error> for AppError {
+    fn from(err: sqlx::Error) -> Self {
+        let error_message = err.to_string();
+        let error_type = "sqlx".to_string();
+        error!(error_message, error_type, "unexpected error");
+        Self::Other(error_message)
+    }
+}
+
 pub type AppResult<T> = Result<T, AppError>;
 
 pub fn internal_error() -> AppError {
@@ -1,28 +1,37 @@
+use crate::auth::jwt::JwtVerifier;
+use crate::auth::session::SessionKeys;
 use crate::auth::AuthenticatedUser;
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, Result};
 use async_graphql::http::GraphiQLSource;
 use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
 use axum::extract::Extension;
 use axum::response;
 use axum::response::IntoResponse;
-use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 use http::HeaderMap;
 use serde::Deserialize;
 use std::str::FromStr;
+use tracing::warn;
 
 use crate::graphql::schema::RaktarSchema;
 
 pub async fn graphql_handler(
     schema: Extension<RaktarSchema>,
+    Extension(verifier): Extension<Option<JwtVerifier>>,
+    Extension(sessions): Extension<Option<SessionKeys>>,
     headers: HeaderMap,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
-    match extract_user_id(&headers) {
+    match authenticate(verifier.as_ref(), sessions.as_ref(), &headers).await {
         Ok(authenticated_user) => {
             let request = req.into_inner().data(authenticated_user);
             schema.execute(request).await.into()
         }
-        Err(_) => {
+        Err(err) => {
+            warn!(
+                error_message = err.to_string(),
+                "failed to authenticate GraphQL request"
+            );
+
             // In local, we allow requests with no authenticated user,
             // mostly to let the frontend to pull the schema.
             // TODO: revise this, we can probably do something smarter
@@ -49,28 +58,25 @@ struct Claims {
     autogen_id: String,
 }
 
-fn extract_user_id(headers: &HeaderMap) -> Result<AuthenticatedUser> {
-    headers
+/// Authenticates the request with either a session the registry issued after logging in
+/// with an OIDC provider, or a token from Cognito.
+async fn authenticate(
+    verifier: Option<&JwtVerifier>,
+    sessions: Option<&SessionKeys>,
+    headers: &HeaderMap,
+) -> Result<AuthenticatedUser> {
+    let header = headers
         .get("Authorization")
-        .and_then(|header| {
-            let token = header.to_str().ok()?;
-            let claims = parse_token(token).ok()?;
-            Some(AuthenticatedUser {
-                id: u32::from_str(&claims.autogen_id).ok()?,
-            })
-        })
-        .ok_or(anyhow!("failed to get authenticated user details"))
-}
+        .ok_or_else(|| anyhow!("the request has no token"))?
+        .to_str()?;
+    let token = header.strip_prefix("Bearer ").unwrap_or(header);
 
-fn parse_token(token: &str) -> Result<Claims> {
-    let mut parts = token.rsplitn(3, '.');
-    if let (Some(_), Some(payload), Some(_), None) =
-        (parts.next(), parts.next(), parts.next(), parts.next())
-    {
-        let decoded = URL_SAFE_NO_PAD.decode(payload)?;
-        let claims = serde_json::from_slice::<Claims>(&decoded)?;
-        Ok(claims)
-    } else {
-        bail!("failed to parse token");
+    if let Some(user_id) = sessions.and_then(|sessions| sessions.verify_session(token).ok()) {
+        return Ok(AuthenticatedUser::new(user_id));
     }
+    let verifier = verifier.ok_or_else(|| anyhow!("verifying tokens is not configured"))?;
+    let claims: Claims = verifier.verify(token).await?;
+    let user_id = u32::from_str(&claims.autogen_id)?;
+
+    Ok(AuthenticatedUser::new(user_id))
 }
@@ -1,9 +1,10 @@
 use anyhow::anyhow;
 use async_graphql::{Context, EmptySubscription, Object, Result, Schema, ID};
+use chrono::{DateTime, Utc};
 use semver::Version;
 use std::str::FromStr;
 
-use crate::auth::{generate_new_token, AuthenticatedUser};
+use crate::auth::{generate_new_token, AuthenticatedUser, PublicKey, TokenScopes};
 use crate::error::AppError;
 use crate::graphql::types::{
     CrateSummary, CrateVersion, DeletedToken, GeneratedToken, Token, User,
@@ -19,6 +20,7 @@ impl Query {
         ctx: &Context<'_>,
         filter: Option<String>,
         limit: Option<usize>,
+        after: Option<String>,
     ) -> Result<Vec<CrateSummary>> {
         let repository = ctx.data::<DynRepository>()?;
 
@@ -27,7 +29,7 @@ impl Query {
             return Err(anyhow!(format!("limit must be less than {}", 20)).into());
         }
         let crates = repository
-            .get_all_crate_details(filter, limit)
+            .get_all_crate_details(filter, limit, after)
             .await?
             .into_iter()
             .map(From::from)
@@ -98,13 +100,28 @@ pub struct Mutation;
 
 #[Object]
 impl Mutation {
-    async fn generate_token(&self, ctx: &Context<'_>, name: String) -> Result<GeneratedToken> {
+    /// Generates a token for Cargo.
+    ///
+    /// The token can be limited to some of `publish-new`, `publish-update`, `yank` and
+    /// `change-owners` with `endpointScopes`, and to crates matching patterns such as
+    /// `raktar-*` with `crateScopes`. Leaving either out doesn't limit the token.
+    /// Tokens with `expiresAt` are rejected from then on.
+    async fn generate_token(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        endpoint_scopes: Option<Vec<String>>,
+        crate_scopes: Option<Vec<String>>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<GeneratedToken> {
         let user = ctx.data::<AuthenticatedUser>()?;
         let repository = ctx.data::<DynRepository>()?;
 
+        check_expiry(expires_at)?;
+        let scopes = parse_scopes(endpoint_scopes, crate_scopes)?;
         let key = generate_new_token();
         let token_item = repository
-            .store_auth_token(key.as_bytes(), name, user.id)
+            .store_auth_token(key.as_bytes(), name, user.id, scopes, expires_at)
             .await?;
         let token: Token = token_item.into();
         let generated_token = GeneratedToken {
@@ -116,6 +133,40 @@ impl Mutation {
         Ok(generated_token)
     }
 
+    /// Registers a public key for Cargo to sign requests with, instead of sending a token.
+    ///
+    /// The key is a P-384 key in PASERK format, such as `k3.public.AmB...`, and can be
+    /// limited the same way as tokens from `generateToken`.
+    async fn register_public_key(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+        public_key: String,
+        endpoint_scopes: Option<Vec<String>>,
+        crate_scopes: Option<Vec<String>>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Token> {
+        let user = ctx.data::<AuthenticatedUser>()?;
+        let repository = ctx.data::<DynRepository>()?;
+
+        let public_key: PublicKey = public_key.parse()?;
+        check_expiry(expires_at)?;
+        let scopes = parse_scopes(endpoint_scopes, crate_scopes)?;
+        if repository
+            .get_auth_token(public_key.id().as_bytes())
+            .await?
+            .is_some()
+        {
+            return Err(anyhow!("the public key is already registered").into());
+        }
+
+        let token_item = repository
+            .store_public_key(&public_key, name, user.id, scopes, expires_at)
+            .await?;
+
+        Ok(token_item.into())
+    }
+
     async fn delete_token(&self, ctx: &Context<'_>, token_id: String) -> Result<DeletedToken> {
         let user = ctx.data::<AuthenticatedUser>()?;
         let repository = ctx.data::<DynRepository>()?;
@@ -128,6 +179,30 @@ impl Mutation {
     }
 }
 
+fn check_expiry(expires_at: Option<DateTime<Utc>>) -> Result<()> {
+    if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Err(anyhow!("the token would already be expired").into());
+    }
+
+    Ok(())
+}
+
+fn parse_scopes(
+    endpoint_scopes: Option<Vec<String>>,
+    crate_scopes: Option<Vec<String>>,
+) -> Result<TokenScopes> {
+    let scopes = TokenScopes {
+        endpoints: endpoint_scopes
+            .map(|scopes| scopes.iter().map(|s| s.parse()).collect())
+            .transpose()?,
+        crates: crate_scopes
+            .map(|patterns| patterns.iter().map(|p| p.parse()).collect())
+            .transpose()?,
+    };
+
+    Ok(scopes)
+}
+
 pub type RaktarSchema = Schema<Query, Mutation, EmptySubscription>;
 
 pub fn build_schema(repository: DynRepository) -> RaktarSchema {
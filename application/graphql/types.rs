@@ -1,8 +1,11 @@
 use crate::error::AppError;
 use async_graphql::{ComplexObject, Context, Result, SimpleObject, ID};
+use chrono::{DateTime, Utc};
 use futures::future::try_join_all;
+use std::collections::BTreeMap;
 
 use crate::models::crate_summary::CrateSummary as CrateSummaryModel;
+use crate::models::download::DownloadCount;
 use crate::models::metadata::Metadata;
 use crate::models::token::Token as TokenModel;
 use crate::models::user::User as UserModel;
@@ -48,6 +51,22 @@ impl CrateSummary {
 
         Ok(versions)
     }
+
+    /// The number of downloads of all versions.
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let repository = ctx.data::<DynRepository>()?;
+        let counts = repository.get_download_counts(&self.name).await?;
+
+        Ok(counts.iter().map(|count| count.downloads).sum())
+    }
+
+    /// The number of downloads of all versions per day, oldest first.
+    async fn daily_downloads(&self, ctx: &Context<'_>) -> Result<Vec<DailyDownloads>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let counts = repository.get_download_counts(&self.name).await?;
+
+        Ok(DailyDownloads::from_counts(counts))
+    }
 }
 
 impl From<CrateSummaryModel> for CrateSummary {
@@ -122,6 +141,57 @@ impl CrateVersion {
             Err(AppError::NonExistentCrate(self.name.clone()).into())
         }
     }
+
+    async fn downloads(&self, ctx: &Context<'_>) -> Result<u64> {
+        let counts = self.download_counts(ctx).await?;
+
+        Ok(counts.iter().map(|count| count.downloads).sum())
+    }
+
+    /// The number of downloads per day, oldest first.
+    async fn daily_downloads(&self, ctx: &Context<'_>) -> Result<Vec<DailyDownloads>> {
+        let counts = self.download_counts(ctx).await?;
+
+        Ok(DailyDownloads::from_counts(counts))
+    }
+}
+
+impl CrateVersion {
+    async fn download_counts(&self, ctx: &Context<'_>) -> Result<Vec<DownloadCount>> {
+        let repository = ctx.data::<DynRepository>()?;
+        let counts = repository
+            .get_download_counts(&self.name)
+            .await?
+            .into_iter()
+            .filter(|count| count.version.to_string() == self.version)
+            .collect();
+
+        Ok(counts)
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct DailyDownloads {
+    /// The day in UTC, formatted as `YYYY-MM-DD`.
+    date: String,
+    downloads: u64,
+}
+
+impl DailyDownloads {
+    fn from_counts(counts: Vec<DownloadCount>) -> Vec<Self> {
+        let mut per_day = BTreeMap::new();
+        for count in counts {
+            *per_day.entry(count.date).or_insert(0) += count.downloads;
+        }
+
+        per_day
+            .into_iter()
+            .map(|(date, downloads)| Self {
+                date: date.to_string(),
+                downloads,
+            })
+            .collect()
+    }
 }
 
 #[derive(SimpleObject)]
@@ -129,6 +199,18 @@ pub struct Token {
     pub id: ID,
     user_id: u32,
     name: String,
+    /// The endpoints the token is limited to, or null if it's not limited.
+    endpoint_scopes: Option<Vec<String>>,
+    /// The crate patterns the token is limited to, or null if it's not limited.
+    crate_scopes: Option<Vec<String>>,
+    /// Null for tokens generated before creation times were recorded.
+    created_at: Option<DateTime<Utc>>,
+    /// Accurate to about an hour, and null if the token hasn't been used yet.
+    last_used_at: Option<DateTime<Utc>>,
+    /// Null if the token doesn't expire.
+    expires_at: Option<DateTime<Utc>>,
+    /// The key Cargo signs requests with, or null for tokens that are sent as they are.
+    public_key: Option<String>,
 }
 
 impl From<TokenModel> for Token {
@@ -137,6 +219,18 @@ impl From<TokenModel> for Token {
             id: item.token_id.into(),
             user_id: item.user_id,
             name: item.name,
+            endpoint_scopes: item
+                .scopes
+                .endpoints
+                .map(|s| s.iter().map(ToString::to_string).collect()),
+            crate_scopes: item
+                .scopes
+                .crates
+                .map(|s| s.iter().map(ToString::to_string).collect()),
+            created_at: item.created_at,
+            last_used_at: item.last_used_at,
+            expires_at: item.expires_at,
+            public_key: item.public_key,
         }
     }
 }
@@ -1,8 +1,11 @@
 pub mod auth;
 pub mod cargo_api;
+pub mod config;
 pub mod error;
 pub mod graphql;
 pub mod models;
 pub mod repository;
 pub mod router;
+pub mod scrub;
+pub mod server;
 pub mod storage;
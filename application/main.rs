@@ -1,43 +1,155 @@
-use std::sync::Arc;
+use clap::{Args, Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
-use aws_sdk_dynamodb::Client;
-use axum::Router;
-use raktar::repository::{DynRepository, DynamoDBRepository};
+#[cfg(not(feature = "local"))]
+use raktar::config::RegistryConfig;
 use raktar::router::build_router;
-use raktar::storage::{DynCrateStorage, S3Storage};
+use raktar::scrub::{scrub, ORPHAN_GRACE_PERIOD};
+use raktar::server::{serve, RegistrySettings, ServerConfig, TlsConfig};
+#[cfg(not(feature = "local"))]
+use raktar::server::{RepositoryBackend, StorageBackend};
+
+/// A private Cargo registry.
+///
+/// Without a command, the registry runs as a Lambda function.
+#[derive(Debug, Parser)]
+#[command(name = "raktar")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Runs the registry as a standalone HTTP(S) server.
+    Serve(Box<ServeArgs>),
+    /// Checks that every version in the index has a stored crate with the right
+    /// checksum, and that every stored crate is in the index.
+    Scrub(ScrubArgs),
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// Path to a TOML config file with the server, backend and registry settings.
+    #[arg(long, short)]
+    config: Option<PathBuf>,
+    /// The address to listen on, such as `0.0.0.0:3026`.
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+    /// Path to the PEM encoded TLS certificate chain, enables HTTPS.
+    #[arg(long, requires = "tls_private_key")]
+    tls_certificate: Option<PathBuf>,
+    /// Path to the PEM encoded TLS private key.
+    #[arg(long, requires = "tls_certificate")]
+    tls_private_key: Option<PathBuf>,
+    #[command(flatten)]
+    registry: RegistrySettings,
+}
+
+#[derive(Debug, Args)]
+struct ScrubArgs {
+    /// Path to a TOML config file with the backend settings, see `serve`.
+    #[arg(long, short)]
+    config: Option<PathBuf>,
+    /// Deletes orphaned crates, and yanks versions whose crate is missing or broken.
+    #[arg(long)]
+    repair: bool,
+}
+
+impl ServeArgs {
+    /// Loads the config file if there is one, and applies the flags on top of it.
+    fn into_config(self) -> anyhow::Result<ServerConfig> {
+        let mut config = match &self.config {
+            Some(path) => ServerConfig::load(path)?,
+            None => ServerConfig::from_env(),
+        };
+        if let Some(listen) = self.listen {
+            config.listen_address = listen;
+        }
+        if let (Some(certificate), Some(private_key)) = (self.tls_certificate, self.tls_private_key)
+        {
+            config.tls = Some(TlsConfig {
+                certificate,
+                private_key,
+            });
+        }
+        config.registry = self.registry.over(config.registry);
+
+        Ok(config)
+    }
+}
 
 #[tokio::main]
-async fn main() {
+async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt().json().init();
 
-    let aws_config = aws_config::from_env().load().await;
-    let db_client = Client::new(&aws_config);
-    let repository = Arc::new(DynamoDBRepository::new_from_env(db_client)) as DynRepository;
-    let storage = Arc::new(S3Storage::new().await) as DynCrateStorage;
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Serve(args)) => run_server(args.into_config()?).await,
+        Some(Command::Scrub(args)) => run_scrub(args).await,
+        None => run_default().await,
+    }
+}
+
+async fn run_server(server_config: ServerConfig) -> anyhow::Result<()> {
+    let repository = server_config.repository.build().await?;
+    let storage = server_config.storage.build().await;
+    let config = server_config.registry_config()?;
 
-    let app = build_router(repository, storage);
+    let app = build_router(repository, storage, config);
 
-    run_app(app).await
+    serve(app, &server_config).await
 }
 
+async fn run_scrub(args: ScrubArgs) -> anyhow::Result<()> {
+    let server_config = match &args.config {
+        Some(path) => ServerConfig::load(path)?,
+        None => ServerConfig::from_env(),
+    };
+    let repository = server_config.repository.build().await?;
+    let storage = server_config.storage.build().await;
+
+    let report = scrub(&repository, &storage, args.repair, ORPHAN_GRACE_PERIOD).await?;
+    for problem in &report.problems {
+        println!("{}", problem);
+    }
+    for repair in &report.repairs {
+        println!("{}", repair);
+    }
+    println!(
+        "checked {} versions, found {} problems",
+        report.checked_versions,
+        report.problems.len()
+    );
+
+    if !report.problems.is_empty() && !args.repair {
+        anyhow::bail!("the index and the stored crates are out of sync");
+    }
+    Ok(())
+}
+
+/// Local builds serve on port 3026 and allow requests from any origin,
+/// so that the frontend can be developed against them.
 #[cfg(feature = "local")]
-async fn run_app(app: Router) {
-    let cors_layer = tower_http::cors::CorsLayer::new()
-        .allow_methods([http::Method::GET, http::Method::POST])
-        .allow_headers(tower_http::cors::Any)
-        .allow_origin(tower_http::cors::Any);
-    let app_with_cors = app.layer(cors_layer);
-    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], 3026));
-    tracing::info!("listening on http://{}", addr);
-    axum::Server::bind(&addr)
-        .serve(app_with_cors.into_make_service())
-        .await
-        .expect("service to start successfully");
+async fn run_default() -> anyhow::Result<()> {
+    let server_config = ServerConfig {
+        cors_allow_any_origin: true,
+        ..ServerConfig::from_env()
+    };
+
+    run_server(server_config).await
 }
 
 #[cfg(not(feature = "local"))]
-async fn run_app(app: Router) {
+async fn run_default() -> anyhow::Result<()> {
+    let repository = RepositoryBackend::from_env().build().await?;
+    let storage = StorageBackend::from_env().build().await;
+    let config = RegistryConfig::from_env()?;
+
+    let app = build_router(repository, storage, config);
+
     lambda_web::run_hyper_on_lambda(app)
         .await
-        .expect("app to run on Lambda successfully")
+        .map_err(|err| anyhow::anyhow!("failed to run on Lambda: {}", err))
 }
@@ -1,4 +1,6 @@
+pub mod crate_name;
 pub mod crate_summary;
+pub mod download;
 pub mod index;
 pub mod metadata;
 pub mod token;
@@ -0,0 +1,194 @@
+//! Rules for crate names, following the ones crates.io enforces.
+use semver::Version;
+
+use crate::error::{AppError, AppResult};
+
+/// The maximum length of a crate name.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Names that can't be used because they clash with the standard library
+/// or with device names that can't be used as file names on Windows.
+const RESERVED_NAMES: &[&str] = &[
+    "alloc",
+    "core",
+    "proc_macro",
+    "std",
+    "test",
+    "con",
+    "prn",
+    "aux",
+    "nul",
+    "com1",
+    "com2",
+    "com3",
+    "com4",
+    "com5",
+    "com6",
+    "com7",
+    "com8",
+    "com9",
+    "lpt1",
+    "lpt2",
+    "lpt3",
+    "lpt4",
+    "lpt5",
+    "lpt6",
+    "lpt7",
+    "lpt8",
+    "lpt9",
+];
+
+/// Checks that the name is allowed to be used for a new crate.
+pub fn validate_crate_name(name: &str) -> AppResult<()> {
+    let invalid = |reason: &str| {
+        Err(AppError::InvalidCrateName(format!(
+            "invalid crate name `{}`: {}",
+            name, reason
+        )))
+    };
+
+    if name.is_empty() {
+        return invalid("the name cannot be empty");
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return invalid(&format!(
+            "the name cannot be longer than {} characters",
+            MAX_NAME_LENGTH
+        ));
+    }
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return invalid("the name must start with an ASCII letter");
+    }
+    if let Some(c) = name
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && *c != '-' && *c != '_')
+    {
+        return invalid(&format!(
+            "the character `{}` is not allowed, only ASCII alphanumerics, `-` and `_` are",
+            c
+        ));
+    }
+    if RESERVED_NAMES.contains(&canonical_crate_name(name).as_str()) {
+        return invalid("the name is reserved");
+    }
+
+    Ok(())
+}
+
+/// The form of the name used to decide whether two crate names are the same.
+///
+/// Like on crates.io, `Foo-Bar` and `foo_bar` are considered to be the same crate.
+pub fn canonical_crate_name(name: &str) -> String {
+    name.to_ascii_lowercase().replace('-', "_")
+}
+
+/// The directory of the crate's file in the index, as described in
+/// https://doc.rust-lang.org/cargo/reference/registry-index.html#index-files
+///
+/// The case of the crate name is kept, Cargo lowercases it where necessary.
+pub fn index_prefix(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    match chars.len() {
+        1 => "1".to_string(),
+        2 => "2".to_string(),
+        3 => format!("3/{}", chars[0]),
+        _ => format!(
+            "{}/{}",
+            chars[0..2].iter().collect::<String>(),
+            chars[2..4].iter().collect::<String>()
+        ),
+    }
+}
+
+/// Checks whether two versions only differ in their build metadata.
+///
+/// Build metadata is ignored when Cargo resolves versions, so `1.0.0+a` and
+/// `1.0.0+b` cannot both be published.
+pub fn is_same_version(version: &Version, other: &Version) -> bool {
+    version.major == other.major
+        && version.minor == other.minor
+        && version.patch == other.patch
+        && version.pre == other.pre
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_valid_names() {
+        for name in ["raktar", "foo-bar", "foo_bar", "Foo2", "a"] {
+            assert!(
+                validate_crate_name(name).is_ok(),
+                "{} should be valid",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let too_long = "a".repeat(MAX_NAME_LENGTH + 1);
+        for name in ["", "1foo", "-foo", "foo.bar", "foo bar", "föö", &too_long] {
+            assert!(
+                matches!(
+                    validate_crate_name(name),
+                    Err(AppError::InvalidCrateName(_))
+                ),
+                "{} should be invalid",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_reserved_names() {
+        for name in ["std", "core", "proc-macro", "CON", "lpt1", "Nul"] {
+            assert!(
+                matches!(
+                    validate_crate_name(name),
+                    Err(AppError::InvalidCrateName(_))
+                ),
+                "{} should be reserved",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_crate_name() {
+        assert_eq!(canonical_crate_name("Foo-Bar"), "foo_bar");
+        assert_eq!(canonical_crate_name("foo_bar"), "foo_bar");
+    }
+
+    #[test]
+    fn test_index_prefix() {
+        assert_eq!(index_prefix("a"), "1");
+        assert_eq!(index_prefix("ab"), "2");
+        assert_eq!(index_prefix("Abc"), "3/A");
+        assert_eq!(index_prefix("Serde"), "Se/rd");
+    }
+
+    #[test]
+    fn test_is_same_version() {
+        let version = Version::from_str("1.0.0+a").unwrap();
+
+        assert!(is_same_version(
+            &version,
+            &Version::from_str("1.0.0+b").unwrap()
+        ));
+        assert!(is_same_version(
+            &version,
+            &Version::from_str("1.0.0").unwrap()
+        ));
+        assert!(!is_same_version(
+            &version,
+            &Version::from_str("1.0.0-alpha").unwrap()
+        ));
+        assert!(!is_same_version(
+            &version,
+            &Version::from_str("1.0.1").unwrap()
+        ));
+    }
+}
@@ -0,0 +1,12 @@
+use chrono::NaiveDate;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+/// The number of times a crate version was downloaded on a given day, in UTC.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DownloadCount {
+    pub crate_name: String,
+    pub version: Version,
+    pub date: NaiveDate,
+    pub downloads: u64,
+}
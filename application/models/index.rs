@@ -1,5 +1,6 @@
 //! Package information in the format information is supposed to be returned
 //! from the index.
+use chrono::{DateTime, Utc};
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -7,7 +8,7 @@ use url::Url;
 
 use crate::models::metadata::{DependencyKind, Metadata, MetadataDependency};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Dependency {
     pub name: String,
     pub req: semver::VersionReq,
@@ -22,7 +23,7 @@ pub struct Dependency {
 
 /// The package information returned from the index as described in the Cargo reference:
 /// https://doc.rust-lang.org/cargo/reference/registry-index.html
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PackageInfo {
     pub name: String,
     pub vers: Version,
@@ -33,6 +34,13 @@ pub struct PackageInfo {
     pub links: Option<String>,
 }
 
+/// The revision of a crate's index file, which changes every time the file changes.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct IndexRevision {
+    pub revision: u64,
+    pub last_modified: DateTime<Utc>,
+}
+
 impl PackageInfo {
     pub fn from_metadata(metadata: Metadata, checksum: &str) -> Self {
         let deps = metadata.deps.into_iter().map(Into::into).collect();
@@ -1,6 +1,85 @@
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::auth::TokenScopes;
+
+/// How often the last use of a token is written, so that a busy token doesn't
+/// cause a write for every request.
+const LAST_USED_RESOLUTION: Duration = Duration::hours(1);
+
 #[derive(Clone, Debug)]
 pub struct Token {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    pub scopes: TokenScopes,
+    /// Not known for tokens created before it was recorded.
+    pub created_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Accurate to within [`LAST_USED_RESOLUTION`].
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// The PASERK public key of a token Cargo signs every request with, in which case
+    /// the token is stored under the id of the key, and no secret is kept.
+    pub public_key: Option<String>,
+}
+
+impl Token {
+    /// A token that's created now.
+    pub fn new(
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            name,
+            user_id,
+            token_id: Uuid::new_v4().hyphenated().to_string(),
+            scopes,
+            created_at: Some(Utc::now()),
+            expires_at,
+            last_used_at: None,
+            public_key: None,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    pub fn needs_last_used_update(&self, now: DateTime<Utc>) -> bool {
+        self.last_used_at
+            .is_none_or(|last_used_at| now - last_used_at >= LAST_USED_RESOLUTION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_token() -> Token {
+        Token::new("test".to_string(), 1, TokenScopes::default(), None)
+    }
+
+    #[test]
+    fn test_expiry() {
+        let now = Utc::now();
+        let mut token = build_token();
+        assert!(!token.is_expired(now));
+
+        token.expires_at = Some(now + Duration::days(1));
+        assert!(!token.is_expired(now));
+        assert!(token.is_expired(now + Duration::days(1)));
+    }
+
+    #[test]
+    fn test_last_used_updates_are_throttled() {
+        let now = Utc::now();
+        let mut token = build_token();
+        assert!(token.needs_last_used_update(now));
+
+        token.last_used_at = Some(now);
+        assert!(!token.needs_last_used_update(now + Duration::minutes(5)));
+        assert!(token.needs_last_used_update(now + Duration::hours(1)));
+    }
 }
@@ -10,6 +10,19 @@ pub struct User {
     pub family_name: String,
 }
 
+impl User {
+    /// An owner that has no user, such as one that was added before ever logging in,
+    /// of whom only the ID is known.
+    pub fn unknown(id: UserId) -> Self {
+        Self {
+            id,
+            login: String::new(),
+            given_name: String::new(),
+            family_name: String::new(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct CognitoUserData {
     pub login: String,
@@ -32,7 +32,7 @@ async fn func(event: LambdaEvent<Value>) -> Result<Value, Error> {
         Ok(trigger_event) => {
             let user_attributes = trigger_event.request.user_attributes;
             match serde_json::from_str::<Vec<Identity>>(&user_attributes.identities) {
-                Ok(identities) => match identities.get(0) {
+                Ok(identities) => match identities.first() {
                     Some(identity) => {
                         let user = CognitoUserData {
                             login: identity.user_id.clone(),
@@ -1,5 +1,9 @@
 mod base;
 pub mod dynamodb;
+pub mod memory;
+pub mod sql;
 
 pub use base::{DynRepository, Repository, UserRepository};
 pub use dynamodb::DynamoDBRepository;
+pub use memory::InMemoryRepository;
+pub use sql::SqlRepository;
@@ -0,0 +1,11 @@
+use crate::error::AppResult;
+use crate::models::download::DownloadCount;
+
+#[async_trait::async_trait]
+pub trait DownloadRepository {
+    /// Adds the downloads to the counts already stored for the same crate version and day.
+    /// Returns the counts that could not be written, so that they can be retried.
+    async fn add_downloads(&self, counts: Vec<DownloadCount>) -> Vec<DownloadCount>;
+    /// Gets the daily download counts of every version of the crate.
+    async fn get_download_counts(&self, crate_name: &str) -> AppResult<Vec<DownloadCount>>;
+}
@@ -1,7 +1,7 @@
 use crate::auth::AuthenticatedUser;
 use crate::error::AppResult;
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::index::{IndexRevision, PackageInfo};
 use crate::models::metadata::Metadata;
 use crate::models::user::User;
 use semver::Version;
@@ -9,6 +9,21 @@ use semver::Version;
 #[async_trait::async_trait]
 pub trait CrateRepository {
     async fn get_package_info(&self, crate_name: &str) -> AppResult<String>;
+    /// Gets the index entry of a single version, whether it's yanked or not.
+    async fn get_package_version_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PackageInfo>>;
+    /// Gets the current revision of the crate's index file, if it has one.
+    ///
+    /// The revision changes every time a version is published, yanked or unyanked.
+    async fn get_index_revision(&self, crate_name: &str) -> AppResult<Option<IndexRevision>>;
+    /// Finds the name an existing crate was published under.
+    ///
+    /// Names are compared after `-`/`_` and case normalization,
+    /// so looking up `foo_bar` finds a crate published as `Foo-Bar`.
+    async fn lookup_crate_name(&self, crate_name: &str) -> AppResult<Option<String>>;
     async fn store_package_info(
         &self,
         crate_name: &str,
@@ -18,13 +33,19 @@ pub trait CrateRepository {
         authenticated_user: &AuthenticatedUser,
     ) -> AppResult<()>;
     async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()>;
+    /// Lists the owners of the crate by ID, including owners that have no user,
+    /// see [`User::unknown`].
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>>;
     async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()>;
     async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>>;
+    /// Lists crates in alphabetical order, optionally only the ones starting with `filter`.
+    ///
+    /// To get the next page, pass the name of the last crate on the previous page as `after`.
     async fn get_all_crate_details(
         &self,
         filter: Option<String>,
         limit: usize,
+        after: Option<String>,
     ) -> AppResult<Vec<CrateSummary>>;
     async fn get_crate_metadata(
         &self,
@@ -1,10 +1,36 @@
+use chrono::{DateTime, Utc};
+
+use crate::auth::{PublicKey, TokenScopes};
 use crate::error::AppResult;
 use crate::models::token::Token;
 
 #[async_trait::async_trait]
 pub trait TokenRepository {
-    async fn store_auth_token(&self, token: &[u8], name: String, user_id: u32) -> AppResult<Token>;
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token>;
+    /// Stores a key Cargo signs tokens with, which is looked up by the id of the key.
+    async fn store_public_key(
+        &self,
+        public_key: &PublicKey,
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token>;
     async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()>;
     async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>>;
     async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>>;
+    /// Records when the token was last used, doing nothing if the token no longer exists.
+    async fn record_token_use(&self, token: &[u8], used_at: DateTime<Utc>) -> AppResult<()>;
+    /// Records the use of a signed token, returning false if it was used before.
+    ///
+    /// The record only needs to be kept until `expires_at`, after which the token
+    /// is too old to be accepted anyway.
+    async fn claim_signed_token(&self, token: &[u8], expires_at: DateTime<Utc>) -> AppResult<bool>;
 }
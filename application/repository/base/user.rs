@@ -3,7 +3,8 @@ use crate::models::user::{CognitoUserData, User, UserId};
 
 #[async_trait::async_trait]
 pub trait UserRepository {
-    /// Used by the pre-token Lambda to ensure the SSO user is up to date in the repository.
+    /// Used by the pre-token Lambda and the OIDC login to ensure the SSO user is up to date
+    /// in the repository.
     ///
     /// This either creates a new user, or checks whether the existing user has the
     /// latest data (e.g. first name and last name being up to date) and bring the
@@ -1,8 +1,11 @@
+mod download;
 mod krate;
 mod token;
 pub mod user;
 
 use aws_sdk_dynamodb::Client;
+use std::sync::Arc;
+use tokio::sync::OnceCell;
 
 use crate::repository::Repository;
 
@@ -10,6 +13,9 @@ use crate::repository::Repository;
 pub struct DynamoDBRepository {
     db_client: Client,
     table_name: String,
+    /// Set once the canonical names of crates published before they were reserved
+    /// have been backfilled.
+    crate_names_backfilled: Arc<OnceCell<()>>,
 }
 
 impl DynamoDBRepository {
@@ -17,6 +23,7 @@ impl DynamoDBRepository {
         Self {
             db_client,
             table_name,
+            crate_names_backfilled: Arc::new(OnceCell::new()),
         }
     }
 
@@ -0,0 +1,100 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use chrono::NaiveDate;
+use futures::stream::{self, StreamExt};
+use futures::TryStreamExt;
+use semver::Version;
+use serde::Deserialize;
+use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
+use tracing::error;
+
+use crate::error::AppResult;
+use crate::models::download::DownloadCount;
+use crate::repository::base::DownloadRepository;
+use crate::repository::dynamodb::krate::get_package_key;
+use crate::repository::DynamoDBRepository;
+
+/// The number of download counts that are written at the same time.
+const MAX_CONCURRENT_UPDATES: usize = 4;
+
+#[async_trait::async_trait]
+impl DownloadRepository for DynamoDBRepository {
+    async fn add_downloads(&self, counts: Vec<DownloadCount>) -> Vec<DownloadCount> {
+        // DynamoDB has no batched updates, so every crate version and day is a separate item,
+        // and only a few of them are written at a time to stay within the table's capacity
+        stream::iter(counts)
+            .map(|count| async move {
+                let result = self
+                    .db_client
+                    .update_item()
+                    .table_name(&self.table_name)
+                    .key("pk", get_package_key(&count.crate_name))
+                    .key("sk", get_download_key(&count.version, &count.date))
+                    .update_expression("SET vers = :vers, #date = :date ADD downloads :downloads")
+                    .expression_attribute_names("#date", "date")
+                    .expression_attribute_values(
+                        ":vers",
+                        AttributeValue::S(count.version.to_string()),
+                    )
+                    .expression_attribute_values(":date", AttributeValue::S(count.date.to_string()))
+                    .expression_attribute_values(
+                        ":downloads",
+                        AttributeValue::N(count.downloads.to_string()),
+                    )
+                    .send()
+                    .await;
+                (count, result)
+            })
+            .buffer_unordered(MAX_CONCURRENT_UPDATES)
+            .filter_map(|(count, result)| async move {
+                let err = result.err()?;
+                let error_message = err.into_service_error().to_string();
+                error!(
+                    error_message,
+                    crate_name = count.crate_name,
+                    version = count.version.to_string(),
+                    "failed to store download count"
+                );
+                Some(count)
+            })
+            .collect()
+            .await
+    }
+
+    async fn get_download_counts(&self, crate_name: &str) -> AppResult<Vec<DownloadCount>> {
+        #[derive(Deserialize)]
+        struct DownloadItem {
+            vers: Version,
+            date: NaiveDate,
+            downloads: u64,
+        }
+
+        let items: Vec<_> = self
+            .db_client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+            .expression_attribute_values(":pk", get_package_key(crate_name))
+            .expression_attribute_values(":prefix", AttributeValue::S("DL#".to_string()))
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+
+        let counts = from_items::<DownloadItem>(items)?
+            .into_iter()
+            .map(|item| DownloadCount {
+                crate_name: crate_name.to_string(),
+                version: item.vers,
+                date: item.date,
+                downloads: item.downloads,
+            })
+            .collect();
+
+        Ok(counts)
+    }
+}
+
+fn get_download_key(version: &Version, date: &NaiveDate) -> AttributeValue {
+    AttributeValue::S(format!("DL#{}#{}", version, date))
+}
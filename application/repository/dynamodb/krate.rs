@@ -1,51 +1,137 @@
 use anyhow::anyhow;
-use aws_sdk_dynamodb::operation::put_item::PutItemError;
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::transact_write_items::builders::TransactWriteItemsFluentBuilder;
 use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
-use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
-use aws_sdk_dynamodb::types::{AttributeValue, Put, ReturnValue, TransactWriteItem};
+use aws_sdk_dynamodb::types::error::TransactionCanceledException;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, CancellationReason, Put, ReturnValuesOnConditionCheckFailure,
+    TransactWriteItem, Update,
+};
 use aws_sdk_dynamodb::Client;
+use chrono::Utc;
+use futures::TryStreamExt;
 use semver::Version;
 use serde::Deserialize;
 use serde_dynamo::aws_sdk_dynamodb_0_27::from_items;
 use serde_dynamo::{from_item, to_item};
 use std::collections::HashMap;
-use tracing::{error, info};
+use std::str::FromStr;
+use tracing::{error, info, warn};
 
 use crate::auth::AuthenticatedUser;
 use crate::error::{AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
 use crate::models::crate_summary::CrateSummary;
-use crate::models::index::PackageInfo;
+use crate::models::index::{IndexRevision, PackageInfo};
 use crate::models::metadata::Metadata;
 use crate::models::user::User;
-use crate::repository::base::CrateRepository;
+use crate::repository::base::{CrateRepository, UserRepository};
 use crate::repository::DynamoDBRepository;
 
 pub static CRATES_PARTITION_KEY: &str = "CRATES";
+pub static CRATE_NAMES_PARTITION_KEY: &str = "CRATE_NAMES";
+/// Marks that every crate has its canonical name reserved, `#` never appears in crate names.
+static CRATE_NAMES_BACKFILLED_KEY: &str = "#BACKFILLED";
 
 #[async_trait::async_trait]
 impl CrateRepository for DynamoDBRepository {
     async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
-        let result = self
+        let items: Vec<_> = self
             .db_client
             .query()
             .table_name(&self.table_name)
             .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
             .expression_attribute_values(":pk", get_package_key(crate_name))
             .expression_attribute_values(":prefix", AttributeValue::S("V#".to_string()))
+            .into_paginator()
+            .items()
             .send()
+            .try_collect()
             .await?;
 
-        match result.items() {
-            None => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
-            Some(items) => {
-                let infos = from_items::<PackageInfo>(items.to_vec())?;
-                let info_strings: Vec<String> = infos
-                    .into_iter()
-                    .map(|info| serde_json::to_string(&info))
-                    .collect::<Result<Vec<_>, serde_json::Error>>()?;
-                Ok(info_strings.join("\n"))
-            }
+        if items.is_empty() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        let infos = from_items::<PackageInfo>(items)?;
+        let info_strings: Vec<String> = infos
+            .into_iter()
+            .map(|info| serde_json::to_string(&info))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_package_version_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PackageInfo>> {
+        let result = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_package_version_key(version))
+            .send()
+            .await?;
+
+        let info = if let Some(item) = result.item().cloned() {
+            from_item(item)?
+        } else {
+            None
+        };
+
+        Ok(info)
+    }
+
+    async fn get_index_revision(&self, crate_name: &str) -> AppResult<Option<IndexRevision>> {
+        let result = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("pk", get_package_key(crate_name))
+            .key("sk", get_index_revision_key())
+            .send()
+            .await?;
+
+        let revision = if let Some(item) = result.item().cloned() {
+            Some(from_item(item)?)
+        } else {
+            None
+        };
+
+        Ok(revision)
+    }
+
+    async fn lookup_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        #[derive(Debug, Deserialize)]
+        struct CrateNameItem {
+            name: String,
         }
+
+        self.crate_names_backfilled
+            .get_or_try_init(|| self.backfill_crate_names())
+            .await?;
+        let result = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key(
+                "pk",
+                AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+            )
+            .key("sk", get_canonical_name_key(crate_name))
+            .send()
+            .await?;
+
+        let name = if let Some(item) = result.item().cloned() {
+            let name_item: CrateNameItem = from_item(item)?;
+            Some(name_item.name)
+        } else {
+            None
+        };
+
+        Ok(name)
     }
 
     async fn store_package_info(
@@ -71,10 +157,11 @@ impl CrateRepository for DynamoDBRepository {
                     crate_name,
                     version,
                     package_info,
+                    metadata,
                     crate_details,
                     true,
                 )
-                .await?;
+                .await
             }
             // this is an update to an existing crate
             Some(old_crate_details) => {
@@ -100,10 +187,11 @@ impl CrateRepository for DynamoDBRepository {
                         crate_name,
                         version,
                         package_info,
+                        metadata,
                         crate_details,
                         false,
                     )
-                    .await?;
+                    .await
                 } else {
                     put_package_version(
                         &self.db_client,
@@ -111,31 +199,37 @@ impl CrateRepository for DynamoDBRepository {
                         crate_name,
                         version,
                         package_info,
+                        metadata,
                     )
-                    .await?;
+                    .await
                 }
             }
         }
-
-        put_package_metadata(&self.db_client, &self.table_name, metadata).await
     }
 
     async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
         let pk = get_package_key(crate_name);
         let sk = get_package_version_key(version);
 
-        self.db_client
-            .update_item()
+        let update = Update::builder()
             .table_name(&self.table_name)
             .key("pk", pk)
             .key("sk", sk)
             .update_expression("SET yanked = :y")
             .condition_expression("attribute_exists(sk)")
             .expression_attribute_values(":y", AttributeValue::Bool(yanked))
+            .build();
+
+        self.db_client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().update(update).build())
+            .transact_items(build_index_revision_update(&self.table_name, crate_name))
             .send()
             .await
             .map_err(|err| match err.into_service_error() {
-                UpdateItemError::ConditionalCheckFailedException(_) => {
+                TransactWriteItemsError::TransactionCanceledException(err)
+                    if is_condition_failure(&err, 0) =>
+                {
                     AppError::NonExistentCrateVersion {
                         crate_name: crate_name.to_string(),
                         version: version.clone(),
@@ -154,36 +248,46 @@ impl CrateRepository for DynamoDBRepository {
     async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
         match get_crate_details(&self.db_client, &self.table_name, crate_name).await? {
             None => Err(AppError::NonExistentPackageInfo(crate_name.to_string())),
-            Some(crate_details) => {
-                let users = crate_details
-                    .owners
-                    .into_iter()
-                    .map(|id| User {
-                        // TODO: this is all dummy logic apart from the ID, it needs to be fixed
-                        id,
-                        login: "dummy".to_string(),
-                        given_name: "dummy".to_string(),
-                        family_name: "dummy".to_string(),
-                    })
-                    .collect();
+            Some(mut crate_details) => {
+                crate_details.owners.sort();
+                let mut users = Vec::with_capacity(crate_details.owners.len());
+                for id in crate_details.owners {
+                    let user = self.get_user_by_id(id).await?;
+                    users.push(user.unwrap_or_else(|| User::unknown(id)));
+                }
                 Ok(users)
             }
         }
     }
 
     async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
-        self.db_client
+        let user_ids = user_ids
+            .iter()
+            .map(|id| u32::from_str(id).map(|id| id.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the owners are a number set, which adding to leaves out the existing owners
+        let result = self
+            .db_client
             .update_item()
             .table_name(&self.table_name)
             .set_key(get_crate_info_key(crate_name.to_string()))
-            .update_expression("ADD #owners = :new_owners")
+            .update_expression("ADD #owners :new_owners")
+            .condition_expression("attribute_exists(sk)")
             .expression_attribute_names("#owners", "owners".to_string())
-            .expression_attribute_values(":new_owners", AttributeValue::Ss(user_ids))
-            .return_values(ReturnValue::UpdatedOld)
+            .expression_attribute_values(":new_owners", AttributeValue::Ns(user_ids))
             .send()
-            .await?;
-
-        Ok(())
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(SdkError::ServiceError(service_err))
+                if service_err.err().is_conditional_check_failed_exception() =>
+            {
+                Err(AppError::NonExistentPackageInfo(crate_name.to_string()))
+            }
+            Err(err) => Err(err.into()),
+        }
     }
 
     async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
@@ -209,33 +313,46 @@ impl CrateRepository for DynamoDBRepository {
         &self,
         filter: Option<String>,
         limit: usize,
+        after: Option<String>,
     ) -> AppResult<Vec<CrateSummary>> {
-        let query_builder = self
-            .db_client
-            .query()
-            .table_name(&self.table_name)
-            .limit(limit as i32);
-
-        let query_builder = if let Some(prefix) = filter {
-            query_builder
-                .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
-                .expression_attribute_values(
-                    ":pk",
-                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
-                )
-                .expression_attribute_values(":prefix", AttributeValue::S(prefix))
-        } else {
-            query_builder
-                .key_condition_expression("pk = :pk")
-                .expression_attribute_values(
-                    ":pk",
-                    AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
-                )
-        };
+        let mut crates = Vec::with_capacity(limit);
+        let mut start_key = after.and_then(get_crate_info_key);
+
+        // a single query returns at most 1 MB, so it can return fewer crates than the limit
+        while crates.len() < limit {
+            let query_builder = self
+                .db_client
+                .query()
+                .table_name(&self.table_name)
+                .limit((limit - crates.len()) as i32)
+                .set_exclusive_start_key(start_key);
+
+            let query_builder = if let Some(prefix) = &filter {
+                query_builder
+                    .key_condition_expression("pk = :pk AND begins_with(sk, :prefix)")
+                    .expression_attribute_values(
+                        ":pk",
+                        AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
+                    )
+                    .expression_attribute_values(":prefix", AttributeValue::S(prefix.clone()))
+            } else {
+                query_builder
+                    .key_condition_expression("pk = :pk")
+                    .expression_attribute_values(
+                        ":pk",
+                        AttributeValue::S(CRATES_PARTITION_KEY.to_string()),
+                    )
+            };
 
-        let output = query_builder.send().await?;
-        let items = output.items().unwrap_or(&[]);
-        let crates = from_items::<CrateSummary>(items.to_vec())?;
+            let output = query_builder.send().await?;
+            let items = output.items().unwrap_or(&[]);
+            crates.extend(from_items::<CrateSummary>(items.to_vec())?);
+
+            match output.last_evaluated_key() {
+                Some(key) => start_key = Some(key.clone()),
+                None => break,
+            }
+        }
 
         Ok(crates)
     }
@@ -269,7 +386,7 @@ impl CrateRepository for DynamoDBRepository {
             vers: Version,
         }
 
-        let output = self
+        let items: Vec<_> = self
             .db_client
             .query()
             .table_name(&self.table_name)
@@ -277,93 +394,135 @@ impl CrateRepository for DynamoDBRepository {
             .expression_attribute_values(":pk", get_package_key(crate_name))
             .expression_attribute_values(":prefix", AttributeValue::S("V#".to_string()))
             .projection_expression("vers")
+            .into_paginator()
+            .items()
             .send()
+            .try_collect()
             .await?;
 
-        Ok(match output.items() {
-            None => vec![],
-            Some(items) => {
-                let parsed_items: Vec<QueryItem> = from_items(items.to_vec())?;
-                parsed_items.into_iter().map(|item| item.vers).collect()
-            }
-        })
+        let parsed_items: Vec<QueryItem> = from_items(items)?;
+        Ok(parsed_items.into_iter().map(|item| item.vers).collect())
     }
 }
 
-async fn put_package_metadata(
-    db_client: &Client,
-    table_name: &str,
-    metadata: Metadata,
-) -> AppResult<()> {
-    let pk = get_package_key(&metadata.name);
-    let sk = get_package_metadata_key(&metadata.vers);
-    let item = to_item(metadata)?;
-    db_client
-        .put_item()
-        .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
-        .send()
-        .await?;
+impl DynamoDBRepository {
+    /// Reserves the canonical names of crates that were published before names were reserved,
+    /// which only has to happen once for the table.
+    async fn backfill_crate_names(&self) -> AppResult<()> {
+        let marker = self
+            .db_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key(
+                "pk",
+                AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+            )
+            .key(
+                "sk",
+                AttributeValue::S(CRATE_NAMES_BACKFILLED_KEY.to_string()),
+            )
+            .send()
+            .await?;
+        if marker.item().is_some() {
+            return Ok(());
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CrateItem {
+            name: String,
+        }
 
-    Ok(())
+        let items: Vec<_> = self
+            .db_client
+            .query()
+            .table_name(&self.table_name)
+            .key_condition_expression("pk = :pk")
+            .expression_attribute_values(":pk", AttributeValue::S(CRATES_PARTITION_KEY.to_string()))
+            .projection_expression("#name")
+            .expression_attribute_names("#name", "name")
+            .into_paginator()
+            .items()
+            .send()
+            .try_collect()
+            .await?;
+        let crates: Vec<CrateItem> = from_items(items)?;
+
+        for krate in &crates {
+            let result = self
+                .db_client
+                .transact_write_items()
+                .transact_items(build_crate_name_put(&self.table_name, &krate.name))
+                .send()
+                .await;
+            match result.map_err(|err| err.into_service_error()) {
+                Ok(_) => {}
+                // crates whose names already clash can't both be reserved, the first one keeps it
+                Err(TransactWriteItemsError::TransactionCanceledException(err))
+                    if is_condition_failure(&err, 0) =>
+                {
+                    warn!(
+                        crate_name = krate.name,
+                        "canonical name is already reserved by another crate"
+                    );
+                }
+                Err(err) => {
+                    let error_message = err.to_string();
+                    error!(error_message, "failed to reserve canonical crate name");
+                    return Err(anyhow!("failed to backfill crate names").into());
+                }
+            }
+        }
+
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item(
+                "pk",
+                AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+            )
+            .item(
+                "sk",
+                AttributeValue::S(CRATE_NAMES_BACKFILLED_KEY.to_string()),
+            )
+            .send()
+            .await?;
+        info!(crates = crates.len(), "backfilled canonical crate names");
+
+        Ok(())
+    }
 }
 
+/// Stores a new version of an existing crate, without touching the crate details.
+///
+/// The index entry and the metadata are written in a single transaction,
+/// so the version never shows up in the index without its metadata.
 async fn put_package_version(
     db_client: &Client,
     table_name: &str,
     crate_name: &str,
     version: &Version,
     package_info: PackageInfo,
+    metadata: Metadata,
 ) -> AppResult<()> {
-    let pk = get_package_key(&package_info.name);
-    let sk = get_package_version_key(&package_info.vers);
-
-    let item = to_item(package_info)?;
-    match db_client
-        .put_item()
-        .table_name(table_name)
-        .set_item(Some(item))
-        .item("pk", pk)
-        .item("sk", sk)
-        .condition_expression("attribute_not_exists(sk)")
-        .send()
-        .await
-    {
-        Ok(_) => {
-            info!(
-                crate_name = crate_name,
-                version = version.to_string(),
-                "persisted package info"
-            );
-            Ok(())
-        }
-        Err(err) => {
-            let err = match err.into_service_error() {
-                PutItemError::ConditionalCheckFailedException(_) => {
-                    AppError::DuplicateCrateVersion {
-                        crate_name: crate_name.to_string(),
-                        version: version.clone(),
-                    }
-                }
-                _ => {
-                    error!("failed to store package info");
-                    anyhow::anyhow!("unexpected error in persisting crate").into()
-                }
-            };
+    let transaction = db_client
+        .transact_write_items()
+        .transact_items(build_package_version_put(table_name, package_info)?)
+        .transact_items(build_package_metadata_put(table_name, metadata)?)
+        .transact_items(build_index_revision_update(table_name, crate_name))
+        .transact_items(build_crate_name_put(table_name, crate_name));
 
-            Err(err)
-        }
-    }
+    send_package_transaction(transaction, crate_name, version).await
 }
 
+/// Stores a new version of a crate together with the updated crate details.
+#[allow(clippy::too_many_arguments)]
 async fn put_package_version_with_new_details(
     db_client: &Client,
     table_name: &str,
     crate_name: &str,
     version: &Version,
     package_info: PackageInfo,
+    metadata: Metadata,
     crate_details: CrateSummary,
     is_new: bool,
 ) -> AppResult<()> {
@@ -383,6 +542,47 @@ async fn put_package_version_with_new_details(
         .build();
     let put_details_item = TransactWriteItem::builder().put(put_item).build();
 
+    // every version reserves the crate's canonical name, so that crates with names that
+    // only differ in case or `-`/`_` cannot be published later
+    let transaction = db_client
+        .transact_write_items()
+        .transact_items(build_package_version_put(table_name, package_info)?)
+        .transact_items(build_package_metadata_put(table_name, metadata)?)
+        .transact_items(build_index_revision_update(table_name, crate_name))
+        .transact_items(put_details_item)
+        .transact_items(build_crate_name_put(table_name, crate_name));
+
+    send_package_transaction(transaction, crate_name, version).await
+}
+
+/// Builds the write that reserves the canonical name of the crate, which has to be the last
+/// item in the transaction.
+///
+/// The name may already be reserved by the crate itself, but not by another crate.
+fn build_crate_name_put(table_name: &str, crate_name: &str) -> TransactWriteItem {
+    let put = Put::builder()
+        .table_name(table_name)
+        .item(
+            "pk",
+            AttributeValue::S(CRATE_NAMES_PARTITION_KEY.to_string()),
+        )
+        .item("sk", get_canonical_name_key(crate_name))
+        .item("name", AttributeValue::S(crate_name.to_string()))
+        .condition_expression("attribute_not_exists(sk) OR #name = :name")
+        .expression_attribute_names("#name", "name")
+        .expression_attribute_values(":name", AttributeValue::S(crate_name.to_string()))
+        // the crate that holds the name is reported back when it's another one
+        .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+        .build();
+
+    TransactWriteItem::builder().put(put).build()
+}
+
+/// Builds the write of the index entry, which has to be the first item in the transaction.
+fn build_package_version_put(
+    table_name: &str,
+    package_info: PackageInfo,
+) -> AppResult<TransactWriteItem> {
     let pk = get_package_key(&package_info.name);
     let sk = get_package_version_key(&package_info.vers);
     let item = to_item(package_info)?;
@@ -391,16 +591,73 @@ async fn put_package_version_with_new_details(
         .set_item(Some(item))
         .item("pk", pk)
         .item("sk", sk)
+        .condition_expression("attribute_not_exists(sk)")
         .build();
-    let put_item = TransactWriteItem::builder().put(put).build();
 
-    match db_client
-        .transact_write_items()
-        .transact_items(put_details_item)
-        .transact_items(put_item)
-        .send()
-        .await
-    {
+    Ok(TransactWriteItem::builder().put(put).build())
+}
+
+fn build_package_metadata_put(
+    table_name: &str,
+    metadata: Metadata,
+) -> AppResult<TransactWriteItem> {
+    let pk = get_package_key(&metadata.name);
+    let sk = get_package_metadata_key(&metadata.vers);
+    let item = to_item(metadata)?;
+    let put = Put::builder()
+        .table_name(table_name)
+        .set_item(Some(item))
+        .item("pk", pk)
+        .item("sk", sk)
+        .build();
+
+    Ok(TransactWriteItem::builder().put(put).build())
+}
+
+/// Builds the update that moves the crate's index file to its next revision.
+fn build_index_revision_update(table_name: &str, crate_name: &str) -> TransactWriteItem {
+    let update = Update::builder()
+        .table_name(table_name)
+        .key("pk", get_package_key(crate_name))
+        .key("sk", get_index_revision_key())
+        .update_expression("ADD revision :one SET last_modified = :now")
+        .expression_attribute_values(":one", AttributeValue::N("1".to_string()))
+        .expression_attribute_values(":now", AttributeValue::S(Utc::now().to_rfc3339()))
+        .build();
+
+    TransactWriteItem::builder().update(update).build()
+}
+
+/// Checks whether the item at the index of a cancelled transaction failed its condition.
+fn is_condition_failure(err: &TransactionCanceledException, index: usize) -> bool {
+    err.cancellation_reasons()
+        .and_then(|reasons| reasons.get(index))
+        .is_some_and(is_conditional_check_failure)
+}
+
+/// Gets the name of the crate that holds the canonical name, when reserving the name
+/// for a new version cancelled the transaction.
+fn get_name_reservation_conflict(err: &TransactionCanceledException) -> Option<String> {
+    let reason = err
+        .cancellation_reasons()?
+        .last()
+        .filter(|reason| is_conditional_check_failure(reason))?;
+    match reason.item()?.get("name")? {
+        AttributeValue::S(name) => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn is_conditional_check_failure(reason: &CancellationReason) -> bool {
+    reason.code() == Some("ConditionalCheckFailed")
+}
+
+async fn send_package_transaction(
+    transaction: TransactWriteItemsFluentBuilder,
+    crate_name: &str,
+    version: &Version,
+) -> AppResult<()> {
+    match transaction.send().await {
         Ok(_) => {
             info!(
                 crate_name = crate_name,
@@ -410,11 +667,27 @@ async fn put_package_version_with_new_details(
             Ok(())
         }
         Err(e) => Err(match e.into_service_error() {
-            TransactWriteItemsError::TransactionCanceledException(_) => {
-                // TODO: how should we handle this? retry? fail?
-                anyhow::anyhow!("write conflict on new crate").into()
+            TransactWriteItemsError::TransactionCanceledException(err) => {
+                if is_condition_failure(&err, 0) {
+                    AppError::DuplicateCrateVersion {
+                        crate_name: crate_name.to_string(),
+                        version: version.clone(),
+                    }
+                } else if let Some(existing_name) = get_name_reservation_conflict(&err) {
+                    // a crate with a similar name was published since the name was checked
+                    AppError::CrateNameConflict {
+                        crate_name: crate_name.to_string(),
+                        existing_name,
+                    }
+                } else {
+                    // TODO: how should we handle this? retry? fail?
+                    anyhow::anyhow!("write conflict on new crate").into()
+                }
+            }
+            _ => {
+                error!("failed to store package info");
+                anyhow::anyhow!("unexpected error in persisting crate").into()
             }
-            _ => anyhow::anyhow!("unexpected error in persisting crate").into(),
         }),
     }
 }
@@ -442,14 +715,22 @@ async fn get_crate_details(
     Ok(details)
 }
 
-fn get_package_key(crate_name: &str) -> AttributeValue {
+pub(super) fn get_package_key(crate_name: &str) -> AttributeValue {
     AttributeValue::S(format!("CRT#{}", crate_name))
 }
 
+fn get_canonical_name_key(crate_name: &str) -> AttributeValue {
+    AttributeValue::S(canonical_crate_name(crate_name))
+}
+
 fn get_package_version_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("V#{}", version))
 }
 
+fn get_index_revision_key() -> AttributeValue {
+    AttributeValue::S("INDEX".to_string())
+}
+
 fn get_package_metadata_key(version: &Version) -> AttributeValue {
     AttributeValue::S(format!("META#{}", version))
 }
@@ -1,11 +1,15 @@
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::update_item::UpdateItemError;
 use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client;
 use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
 use serde_dynamo::{from_item, from_items, to_item};
 use uuid::Uuid;
 
-use crate::auth::hash;
+use crate::auth::{hash, CratePattern, EndpointScope, PublicKey, TokenScopes};
 use crate::error::AppResult;
 use crate::models::token::Token;
 use crate::models::user::UserId;
@@ -14,17 +18,37 @@ use crate::repository::DynamoDBRepository;
 
 #[async_trait::async_trait]
 impl TokenRepository for DynamoDBRepository {
-    async fn store_auth_token(&self, token: &[u8], name: String, user_id: u32) -> AppResult<Token> {
-        let token_item = TokenItem::new(token, name, user_id);
-        let item = to_item(token_item.clone())?;
-        self.db_client
-            .put_item()
-            .table_name(&self.table_name)
-            .set_item(Some(item))
-            .send()
-            .await?;
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = TokenItem::new(token, name, user_id, scopes, expires_at);
+        self.put_token(token_item).await
+    }
 
-        Ok(token_item.into())
+    async fn store_public_key(
+        &self,
+        public_key: &PublicKey,
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = TokenItem {
+            public_key: Some(public_key.to_string()),
+            ..TokenItem::new(
+                public_key.id().as_bytes(),
+                name,
+                user_id,
+                scopes,
+                expires_at,
+            )
+        };
+        self.put_token(token_item).await
     }
 
     async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
@@ -70,6 +94,80 @@ impl TokenRepository for DynamoDBRepository {
 
         Ok(token)
     }
+
+    async fn record_token_use(&self, token: &[u8], used_at: DateTime<Utc>) -> AppResult<()> {
+        let result = self
+            .db_client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("pk", AttributeValue::S(TokenItem::get_pk(token)))
+            .key("sk", AttributeValue::S(TokenItem::get_sk()))
+            .update_expression("SET last_used_at = :used_at")
+            .condition_expression("attribute_exists(pk)")
+            .expression_attribute_values(":used_at", AttributeValue::S(used_at.to_rfc3339()))
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(()),
+            // the token was deleted in the meantime
+            Err(err) if is_conditional_check_failure(&err) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn claim_signed_token(&self, token: &[u8], expires_at: DateTime<Utc>) -> AppResult<bool> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(hash(token));
+        // the table's time to live removes the item eventually, until then
+        // an expired item doesn't count
+        let result = self
+            .db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("pk", AttributeValue::S(format!("SIG#{}", encoded)))
+            .item("sk", AttributeValue::S("SIG".to_string()))
+            .item("ttl", AttributeValue::N(expires_at.timestamp().to_string()))
+            .condition_expression("attribute_not_exists(pk) OR #ttl <= :now")
+            .expression_attribute_names("#ttl", "ttl")
+            .expression_attribute_values(
+                ":now",
+                AttributeValue::N(Utc::now().timestamp().to_string()),
+            )
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(SdkError::ServiceError(service_err))
+                if service_err.err().is_conditional_check_failed_exception() =>
+            {
+                Ok(false)
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+impl DynamoDBRepository {
+    async fn put_token(&self, token_item: TokenItem) -> AppResult<Token> {
+        let item = to_item(token_item.clone())?;
+        self.db_client
+            .put_item()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .send()
+            .await?;
+
+        Ok(token_item.into())
+    }
+}
+
+fn is_conditional_check_failure(err: &SdkError<UpdateItemError>) -> bool {
+    matches!(
+        err,
+        SdkError::ServiceError(service_err)
+            if service_err.err().is_conditional_check_failed_exception()
+    )
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -79,16 +177,40 @@ struct TokenItem {
     pub name: String,
     pub user_id: u32,
     pub token_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint_scopes: Option<Vec<EndpointScope>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crate_scopes: Option<Vec<CratePattern>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
 }
 
 impl TokenItem {
-    fn new(token: &[u8], name: String, user_id: u32) -> Self {
+    fn new(
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Self {
         Self {
             pk: Self::get_pk(token),
             sk: Self::get_sk(),
             name,
             user_id,
             token_id: Uuid::new_v4().hyphenated().to_string(),
+            endpoint_scopes: scopes.endpoints,
+            crate_scopes: scopes.crates,
+            created_at: Some(Utc::now()),
+            expires_at,
+            last_used_at: None,
+            public_key: None,
         }
     }
 
@@ -97,16 +219,18 @@ impl TokenItem {
         table_name: &str,
         user_id: UserId,
     ) -> AppResult<Vec<TokenItem>> {
-        let output = db_client
+        let items: Vec<_> = db_client
             .query()
             .table_name(table_name)
             .index_name("user_tokens")
             .key_condition_expression("user_id = :user_id")
             .expression_attribute_values(":user_id", AttributeValue::N(user_id.to_string()))
+            .into_paginator()
+            .items()
             .send()
+            .try_collect()
             .await?;
 
-        let items = output.items().map(|items| items.to_vec()).unwrap_or(vec![]);
         Ok(from_items(items)?)
     }
 
@@ -126,6 +250,14 @@ impl From<TokenItem> for Token {
             name: item.name,
             user_id: item.user_id,
             token_id: item.token_id,
+            scopes: TokenScopes {
+                endpoints: item.endpoint_scopes,
+                crates: item.crate_scopes,
+            },
+            created_at: item.created_at,
+            expires_at: item.expires_at,
+            last_used_at: item.last_used_at,
+            public_key: item.public_key,
         }
     }
 }
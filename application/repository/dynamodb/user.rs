@@ -1,5 +1,6 @@
 use aws_sdk_dynamodb::types::{AttributeValue, Put, TransactWriteItem};
 use aws_sdk_dynamodb::Client;
+use futures::TryStreamExt;
 use serde_dynamo::{from_item, from_items, to_item};
 use std::str::FromStr;
 use tracing::info;
@@ -65,23 +66,21 @@ impl UserRepository for DynamoDBRepository {
     }
 
     async fn get_users(&self) -> AppResult<Vec<User>> {
-        let output = self
+        let items: Vec<_> = self
             .db_client
             .query()
             .table_name(&self.table_name)
             .key_condition_expression("pk = :pk and begins_with(sk, :prefix)")
             .expression_attribute_values(":pk", AttributeValue::S("USERS".to_string()))
             .expression_attribute_values(":prefix", AttributeValue::S("ID#".to_string()))
+            .into_paginator()
+            .items()
             .send()
+            .try_collect()
             .await?;
 
-        match output.items() {
-            None => Ok(vec![]),
-            Some(items) => {
-                let users = from_items(items.to_vec())?;
-                Ok(users)
-            }
-        }
+        let users = from_items(items)?;
+        Ok(users)
     }
 }
 
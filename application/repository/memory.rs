@@ -0,0 +1,52 @@
+//! A repository that keeps everything in memory.
+//!
+//! It behaves the same way as [`DynamoDBRepository`](crate::repository::DynamoDBRepository),
+//! which makes it useful for tests and for running the registry locally without a database.
+//! Everything is lost when the repository is dropped.
+mod download;
+mod krate;
+mod token;
+mod user;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use semver::Version;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::models::token::Token;
+use crate::models::user::{User, UserId};
+use crate::repository::Repository;
+
+use krate::CrateEntry;
+
+#[derive(Clone, Default)]
+pub struct InMemoryRepository {
+    crates: Arc<RwLock<Crates>>,
+    downloads: Arc<RwLock<Downloads>>,
+    users: Arc<RwLock<BTreeMap<UserId, User>>>,
+    /// Tokens keyed by the hash of the token.
+    tokens: Arc<RwLock<HashMap<Vec<u8>, Token>>>,
+    /// When the signed tokens that were used can be forgotten, keyed by their hash.
+    signed_tokens: Arc<RwLock<HashMap<Vec<u8>, DateTime<Utc>>>>,
+}
+
+/// Download counts keyed by crate name, version and day.
+type Downloads = BTreeMap<(String, Version, NaiveDate), u64>;
+
+#[derive(Default)]
+struct Crates {
+    /// Crates keyed by the name they were published under.
+    entries: BTreeMap<String, CrateEntry>,
+    /// Crate names keyed by their canonical name.
+    canonical_names: HashMap<String, String>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl Repository for InMemoryRepository {}
@@ -0,0 +1,34 @@
+use crate::error::AppResult;
+use crate::models::download::DownloadCount;
+use crate::repository::base::DownloadRepository;
+use crate::repository::InMemoryRepository;
+
+#[async_trait::async_trait]
+impl DownloadRepository for InMemoryRepository {
+    async fn add_downloads(&self, counts: Vec<DownloadCount>) -> Vec<DownloadCount> {
+        let mut downloads = self.downloads.write().await;
+        for count in counts {
+            *downloads
+                .entry((count.crate_name, count.version, count.date))
+                .or_default() += count.downloads;
+        }
+
+        Vec::new()
+    }
+
+    async fn get_download_counts(&self, crate_name: &str) -> AppResult<Vec<DownloadCount>> {
+        let downloads = self.downloads.read().await;
+        let counts = downloads
+            .iter()
+            .filter(|((name, _, _), _)| name == crate_name)
+            .map(|((name, version, date), downloads)| DownloadCount {
+                crate_name: name.clone(),
+                version: version.clone(),
+                date: *date,
+                downloads: *downloads,
+            })
+            .collect();
+
+        Ok(counts)
+    }
+}
@@ -0,0 +1,287 @@
+use chrono::Utc;
+use semver::Version;
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::index::{IndexRevision, PackageInfo};
+use crate::models::metadata::Metadata;
+use crate::models::user::User;
+use crate::repository::base::CrateRepository;
+use crate::repository::InMemoryRepository;
+
+pub struct CrateEntry {
+    summary: CrateSummary,
+    /// Index entries keyed by the version string, so they are ordered the same way
+    /// as the versions in the DynamoDB table.
+    versions: BTreeMap<String, PackageInfo>,
+    metadata: HashMap<Version, Metadata>,
+    revision: IndexRevision,
+}
+
+impl CrateEntry {
+    fn new(summary: CrateSummary) -> Self {
+        Self {
+            summary,
+            versions: BTreeMap::new(),
+            metadata: HashMap::new(),
+            revision: IndexRevision {
+                revision: 0,
+                last_modified: Utc::now(),
+            },
+        }
+    }
+
+    fn bump_revision(&mut self) {
+        self.revision = IndexRevision {
+            revision: self.revision.revision + 1,
+            last_modified: Utc::now(),
+        };
+    }
+}
+
+#[async_trait::async_trait]
+impl CrateRepository for InMemoryRepository {
+    async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
+        let crates = self.crates.read().await;
+        let versions = crates
+            .entries
+            .get(crate_name)
+            .map(|entry| &entry.versions)
+            .filter(|versions| !versions.is_empty())
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+        let info_strings: Vec<String> = versions
+            .values()
+            .map(serde_json::to_string)
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_package_version_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PackageInfo>> {
+        let crates = self.crates.read().await;
+        let info = crates
+            .entries
+            .get(crate_name)
+            .and_then(|entry| entry.versions.get(&version.to_string()))
+            .cloned();
+
+        Ok(info)
+    }
+
+    async fn get_index_revision(&self, crate_name: &str) -> AppResult<Option<IndexRevision>> {
+        let crates = self.crates.read().await;
+        let revision = crates
+            .entries
+            .get(crate_name)
+            .map(|entry| entry.revision.clone());
+
+        Ok(revision)
+    }
+
+    async fn lookup_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let crates = self.crates.read().await;
+        let name = crates
+            .canonical_names
+            .get(&canonical_crate_name(crate_name))
+            .cloned();
+
+        Ok(name)
+    }
+
+    async fn store_package_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        package_info: PackageInfo,
+        metadata: Metadata,
+        authenticated_user: &AuthenticatedUser,
+    ) -> AppResult<()> {
+        let mut crates = self.crates.write().await;
+        let canonical_name = canonical_crate_name(crate_name);
+
+        let entry = match crates.entries.get_mut(crate_name) {
+            // this is a brand new crate
+            None => {
+                if let Some(existing_name) = crates.canonical_names.get(&canonical_name) {
+                    return Err(AppError::CrateNameConflict {
+                        crate_name: crate_name.to_string(),
+                        existing_name: existing_name.clone(),
+                    });
+                }
+
+                let summary = CrateSummary {
+                    name: crate_name.to_string(),
+                    owners: vec![authenticated_user.id],
+                    max_version: package_info.vers.clone(),
+                    description: metadata.description.clone().unwrap_or("".to_string()),
+                };
+                crates
+                    .canonical_names
+                    .insert(canonical_name, crate_name.to_string());
+                crates
+                    .entries
+                    .entry(crate_name.to_string())
+                    .or_insert(CrateEntry::new(summary))
+            }
+            // this is an update to an existing crate
+            Some(entry) => {
+                if !entry.summary.owners.contains(&authenticated_user.id) {
+                    return Err(AppError::Unauthorized(
+                        "user is not an owner of this package".to_string(),
+                    ));
+                }
+                if entry.versions.contains_key(&version.to_string()) {
+                    return Err(AppError::DuplicateCrateVersion {
+                        crate_name: crate_name.to_string(),
+                        version: version.clone(),
+                    });
+                }
+
+                // only a newer version moves the head state of the crate
+                if entry.summary.max_version < package_info.vers {
+                    entry.summary.max_version = package_info.vers.clone();
+                    entry.summary.description =
+                        metadata.description.clone().unwrap_or("".to_string());
+                }
+                entry
+            }
+        };
+
+        entry.versions.insert(version.to_string(), package_info);
+        entry.metadata.insert(version.clone(), metadata);
+        entry.bump_revision();
+
+        info!(
+            crate_name = crate_name,
+            version = version.to_string(),
+            "persisted package info"
+        );
+        Ok(())
+    }
+
+    async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
+        let mut crates = self.crates.write().await;
+        let entry = crates.entries.get_mut(crate_name);
+        let Some(entry) = entry.filter(|e| e.versions.contains_key(&version.to_string())) else {
+            return Err(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            });
+        };
+
+        if let Some(package_info) = entry.versions.get_mut(&version.to_string()) {
+            package_info.yanked = yanked;
+        }
+        entry.bump_revision();
+
+        Ok(())
+    }
+
+    async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
+        let crates = self.crates.read().await;
+        let entry = crates
+            .entries
+            .get(crate_name)
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+
+        let users = self.users.read().await;
+        let mut owners: Vec<_> = entry
+            .summary
+            .owners
+            .iter()
+            .map(|id| users.get(id).cloned().unwrap_or_else(|| User::unknown(*id)))
+            .collect();
+        owners.sort_by_key(|owner| owner.id);
+
+        Ok(owners)
+    }
+
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+        let user_ids = user_ids
+            .iter()
+            .map(|id| u32::from_str(id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut crates = self.crates.write().await;
+        let entry = crates
+            .entries
+            .get_mut(crate_name)
+            .ok_or_else(|| AppError::NonExistentPackageInfo(crate_name.to_string()))?;
+        for user_id in user_ids {
+            if !entry.summary.owners.contains(&user_id) {
+                entry.summary.owners.push(user_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
+        let crates = self.crates.read().await;
+        let summary = crates
+            .entries
+            .get(crate_name)
+            .map(|entry| entry.summary.clone());
+
+        Ok(summary)
+    }
+
+    async fn get_all_crate_details(
+        &self,
+        filter: Option<String>,
+        limit: usize,
+        after: Option<String>,
+    ) -> AppResult<Vec<CrateSummary>> {
+        let crates = self.crates.read().await;
+        let summaries = crates
+            .entries
+            .values()
+            .map(|entry| &entry.summary)
+            .filter(|summary| after.as_ref().is_none_or(|after| &summary.name > after))
+            .filter(|summary| {
+                filter
+                    .as_ref()
+                    .is_none_or(|prefix| summary.name.starts_with(prefix))
+            })
+            .take(limit)
+            .cloned()
+            .collect();
+
+        Ok(summaries)
+    }
+
+    async fn get_crate_metadata(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Metadata>> {
+        let crates = self.crates.read().await;
+        let metadata = crates
+            .entries
+            .get(crate_name)
+            .and_then(|entry| entry.metadata.get(version))
+            .cloned();
+
+        Ok(metadata)
+    }
+
+    async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>> {
+        let crates = self.crates.read().await;
+        let versions = crates
+            .entries
+            .get(crate_name)
+            .map(|entry| entry.versions.values().map(|v| v.vers.clone()).collect())
+            .unwrap_or_default();
+
+        Ok(versions)
+    }
+}
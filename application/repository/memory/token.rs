@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+
+use crate::auth::{hash, PublicKey, TokenScopes};
+use crate::error::AppResult;
+use crate::models::token::Token;
+use crate::repository::base::TokenRepository;
+use crate::repository::InMemoryRepository;
+
+#[async_trait::async_trait]
+impl TokenRepository for InMemoryRepository {
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = Token::new(name, user_id, scopes, expires_at);
+        let mut tokens = self.tokens.write().await;
+        tokens.insert(hash(token), token_item.clone());
+
+        Ok(token_item)
+    }
+
+    async fn store_public_key(
+        &self,
+        public_key: &PublicKey,
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = Token {
+            public_key: Some(public_key.to_string()),
+            ..Token::new(name, user_id, scopes, expires_at)
+        };
+        let mut tokens = self.tokens.write().await;
+        tokens.insert(hash(public_key.id().as_bytes()), token_item.clone());
+
+        Ok(token_item)
+    }
+
+    async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
+        let mut tokens = self.tokens.write().await;
+        tokens.retain(|_, token| !(token.user_id == user_id && token.token_id == token_id));
+
+        Ok(())
+    }
+
+    async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>> {
+        let tokens = self.tokens.read().await;
+        let user_tokens = tokens
+            .values()
+            .filter(|token| token.user_id == user_id)
+            .cloned()
+            .collect();
+
+        Ok(user_tokens)
+    }
+
+    async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>> {
+        let tokens = self.tokens.read().await;
+        Ok(tokens.get(&hash(token)).cloned())
+    }
+
+    async fn record_token_use(&self, token: &[u8], used_at: DateTime<Utc>) -> AppResult<()> {
+        let mut tokens = self.tokens.write().await;
+        if let Some(token_item) = tokens.get_mut(&hash(token)) {
+            token_item.last_used_at = Some(used_at);
+        }
+
+        Ok(())
+    }
+
+    async fn claim_signed_token(&self, token: &[u8], expires_at: DateTime<Utc>) -> AppResult<bool> {
+        let now = Utc::now();
+        let mut signed_tokens = self.signed_tokens.write().await;
+        signed_tokens.retain(|_, expires_at| *expires_at > now);
+
+        Ok(signed_tokens.insert(hash(token), expires_at).is_none())
+    }
+}
@@ -0,0 +1,46 @@
+use tracing::info;
+
+use crate::error::AppResult;
+use crate::models::user::{CognitoUserData, User, UserId};
+use crate::repository::base::UserRepository;
+use crate::repository::InMemoryRepository;
+
+#[async_trait::async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn update_or_create_user(&self, user_data: CognitoUserData) -> AppResult<User> {
+        let mut users = self.users.write().await;
+        let existing_user = users
+            .values()
+            .find(|user| user.login == user_data.login)
+            .cloned();
+
+        match existing_user {
+            None => {
+                info!("user not found, creating new user");
+                let next_id = users.keys().next_back().map_or(1, |id| id + 1);
+                let user = user_data.into_user(next_id);
+                users.insert(next_id, user.clone());
+                Ok(user)
+            }
+            Some(user) => {
+                // if the existing user data is out of sync, update it
+                let existing_user_data: CognitoUserData = user.clone().into();
+                if existing_user_data != user_data {
+                    users.insert(user.id, user_data.into_user(user.id));
+                }
+
+                Ok(user)
+            }
+        }
+    }
+
+    async fn get_user_by_id(&self, user_id: UserId) -> AppResult<Option<User>> {
+        let users = self.users.read().await;
+        Ok(users.get(&user_id).cloned())
+    }
+
+    async fn get_users(&self) -> AppResult<Vec<User>> {
+        let users = self.users.read().await;
+        Ok(users.values().cloned().collect())
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::anyhow;
+use chrono::NaiveDate;
+use sqlx::FromRow;
+use tracing::error;
+
+use crate::error::AppResult;
+use crate::models::download::DownloadCount;
+use crate::repository::base::DownloadRepository;
+use crate::repository::sql::parse_version;
+use crate::repository::SqlRepository;
+
+#[derive(FromRow)]
+struct DownloadRow {
+    crate_name: String,
+    version: String,
+    date: String,
+    downloads: i64,
+}
+
+impl DownloadRow {
+    fn into_download_count(self) -> AppResult<DownloadCount> {
+        Ok(DownloadCount {
+            version: parse_version(&self.version)?,
+            date: NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+                .map_err(|_| anyhow!("invalid date {} in database", self.date))?,
+            downloads: u64::try_from(self.downloads)
+                .map_err(|_| anyhow!("invalid download count {} in database", self.downloads))?,
+            crate_name: self.crate_name,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl DownloadRepository for SqlRepository {
+    async fn add_downloads(&self, counts: Vec<DownloadCount>) -> Vec<DownloadCount> {
+        // the counts are written in a single transaction, so either all of them are written or none
+        match self.write_downloads(&counts).await {
+            Ok(()) => Vec::new(),
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to store download counts");
+                counts
+            }
+        }
+    }
+
+    async fn get_download_counts(&self, crate_name: &str) -> AppResult<Vec<DownloadCount>> {
+        let rows: Vec<DownloadRow> = sqlx::query_as(
+            "SELECT crate_name, version, date, downloads FROM version_downloads
+            WHERE crate_name = $1 ORDER BY version, date",
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(DownloadRow::into_download_count)
+            .collect()
+    }
+}
+
+impl SqlRepository {
+    async fn write_downloads(&self, counts: &[DownloadCount]) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        for count in counts {
+            let downloads = i64::try_from(count.downloads)
+                .map_err(|_| anyhow!("download count {} is too large", count.downloads))?;
+            sqlx::query(
+                "INSERT INTO version_downloads (crate_name, version, date, downloads)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (crate_name, version, date)
+                DO UPDATE SET downloads = version_downloads.downloads + excluded.downloads",
+            )
+            .bind(&count.crate_name)
+            .bind(count.version.to_string())
+            .bind(count.date.format("%Y-%m-%d").to_string())
+            .bind(downloads)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
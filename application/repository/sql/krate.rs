@@ -0,0 +1,461 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use semver::Version;
+use sqlx::{Any, FromRow, Transaction};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::auth::AuthenticatedUser;
+use crate::error::{AppError, AppResult};
+use crate::models::crate_name::canonical_crate_name;
+use crate::models::crate_summary::CrateSummary;
+use crate::models::index::{IndexRevision, PackageInfo};
+use crate::models::metadata::Metadata;
+use crate::models::user::User;
+use crate::repository::base::CrateRepository;
+use crate::repository::sql::user::UserRow;
+use crate::repository::sql::{is_unique_violation, parse_version, to_user_id};
+use crate::repository::SqlRepository;
+
+#[derive(FromRow)]
+struct CrateRow {
+    name: String,
+    max_version: String,
+    description: String,
+}
+
+#[derive(FromRow)]
+struct VersionRow {
+    crate_name: String,
+    version: String,
+    checksum: String,
+    yanked: i64,
+    links: Option<String>,
+    dependencies: String,
+    features: String,
+}
+
+impl VersionRow {
+    fn into_package_info(self) -> AppResult<PackageInfo> {
+        Ok(PackageInfo {
+            name: self.crate_name,
+            vers: parse_version(&self.version)?,
+            deps: serde_json::from_str(&self.dependencies)?,
+            cksum: self.checksum,
+            features: serde_json::from_str(&self.features)?,
+            yanked: self.yanked != 0,
+            links: self.links,
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct RevisionRow {
+    index_revision: i64,
+    index_last_modified: String,
+}
+
+#[async_trait::async_trait]
+impl CrateRepository for SqlRepository {
+    async fn get_package_info(&self, crate_name: &str) -> AppResult<String> {
+        let rows: Vec<VersionRow> = sqlx::query_as(
+            // the `Any` driver can't decode SQLite booleans, so `yanked` is read as an integer
+            "SELECT crate_name, version, checksum, \
+             CAST(CASE WHEN yanked THEN 1 ELSE 0 END AS BIGINT) AS yanked, \
+             links, dependencies, features FROM crate_versions WHERE crate_name = $1",
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        if rows.is_empty() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        let mut infos = rows
+            .into_iter()
+            .map(VersionRow::into_package_info)
+            .collect::<AppResult<Vec<_>>>()?;
+        infos.sort_by(|a, b| a.vers.cmp(&b.vers));
+        let info_strings: Vec<String> = infos
+            .into_iter()
+            .map(|info| serde_json::to_string(&info))
+            .collect::<Result<Vec<_>, serde_json::Error>>()?;
+        Ok(info_strings.join("\n"))
+    }
+
+    async fn get_package_version_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<PackageInfo>> {
+        let row: Option<VersionRow> = sqlx::query_as(
+            "SELECT crate_name, version, checksum, \
+             CAST(CASE WHEN yanked THEN 1 ELSE 0 END AS BIGINT) AS yanked, \
+             links, dependencies, features FROM crate_versions \
+             WHERE crate_name = $1 AND version = $2",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(VersionRow::into_package_info).transpose()
+    }
+
+    async fn get_index_revision(&self, crate_name: &str) -> AppResult<Option<IndexRevision>> {
+        let row: Option<RevisionRow> = sqlx::query_as(
+            "SELECT index_revision, index_last_modified FROM crates WHERE name = $1",
+        )
+        .bind(crate_name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let revision = match row {
+            None => None,
+            Some(row) => Some(IndexRevision {
+                revision: u64::try_from(row.index_revision)
+                    .map_err(|_| anyhow!("invalid index revision"))?,
+                last_modified: DateTime::parse_from_rfc3339(&row.index_last_modified)
+                    .map_err(|_| anyhow!("invalid index modification time"))?
+                    .with_timezone(&Utc),
+            }),
+        };
+
+        Ok(revision)
+    }
+
+    async fn lookup_crate_name(&self, crate_name: &str) -> AppResult<Option<String>> {
+        let name: Option<(String,)> =
+            sqlx::query_as("SELECT name FROM crates WHERE canonical_name = $1")
+                .bind(canonical_crate_name(crate_name))
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(name.map(|(name,)| name))
+    }
+
+    async fn store_package_info(
+        &self,
+        crate_name: &str,
+        version: &Version,
+        package_info: PackageInfo,
+        metadata: Metadata,
+        authenticated_user: &AuthenticatedUser,
+    ) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let description = metadata.description.clone().unwrap_or("".to_string());
+
+        match get_crate_row(&mut tx, crate_name).await? {
+            // this is a brand new crate
+            None => {
+                let result = sqlx::query(
+                    "INSERT INTO crates \
+                     (name, canonical_name, max_version, description, index_revision, index_last_modified) \
+                     VALUES ($1, $2, $3, $4, $5, $6)",
+                )
+                .bind(crate_name)
+                .bind(canonical_crate_name(crate_name))
+                .bind(package_info.vers.to_string())
+                .bind(description)
+                .bind(0_i64)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&mut *tx)
+                .await;
+                match result {
+                    Ok(_) => {}
+                    Err(err) if is_unique_violation(&err) => {
+                        // the transaction can't be used after a failed statement
+                        drop(tx);
+                        return Err(match self.lookup_crate_name(crate_name).await? {
+                            Some(existing_name) if existing_name != crate_name => {
+                                AppError::CrateNameConflict {
+                                    crate_name: crate_name.to_string(),
+                                    existing_name,
+                                }
+                            }
+                            _ => anyhow!("write conflict on new crate").into(),
+                        });
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+                sqlx::query("INSERT INTO crate_owners (crate_name, user_id) VALUES ($1, $2)")
+                    .bind(crate_name)
+                    .bind(i64::from(authenticated_user.id))
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            // this is an update to an existing crate
+            Some(crate_row) => {
+                let owners = get_owner_ids(&mut tx, crate_name).await?;
+                if !owners.contains(&authenticated_user.id) {
+                    return Err(AppError::Unauthorized(
+                        "user is not an owner of this package".to_string(),
+                    ));
+                }
+
+                // only a newer version moves the head state of the crate
+                if parse_version(&crate_row.max_version)? < package_info.vers {
+                    sqlx::query(
+                        "UPDATE crates SET max_version = $1, description = $2 WHERE name = $3",
+                    )
+                    .bind(package_info.vers.to_string())
+                    .bind(description)
+                    .bind(crate_name)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+            }
+        }
+
+        sqlx::query(
+            "INSERT INTO crate_versions \
+             (crate_name, version, checksum, yanked, links, dependencies, features, metadata) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .bind(&package_info.cksum)
+        .bind(package_info.yanked)
+        .bind(package_info.links.clone())
+        .bind(serde_json::to_string(&package_info.deps)?)
+        .bind(serde_json::to_string(&package_info.features)?)
+        .bind(serde_json::to_string(&metadata)?)
+        .execute(&mut *tx)
+        .await
+        .map_err(|err| {
+            if is_unique_violation(&err) {
+                AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version: version.clone(),
+                }
+            } else {
+                AppError::from(err)
+            }
+        })?;
+
+        bump_index_revision(&mut tx, crate_name).await?;
+        tx.commit().await?;
+
+        info!(
+            crate_name = crate_name,
+            version = version.to_string(),
+            "persisted package info"
+        );
+        Ok(())
+    }
+
+    async fn set_yanked(&self, crate_name: &str, version: &Version, yanked: bool) -> AppResult<()> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE crate_versions SET yanked = $1 WHERE crate_name = $2 AND version = $3",
+        )
+        .bind(yanked)
+        .bind(crate_name)
+        .bind(version.to_string())
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(AppError::NonExistentCrateVersion {
+                crate_name: crate_name.to_string(),
+                version: version.clone(),
+            });
+        }
+
+        bump_index_revision(&mut tx, crate_name).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn list_owners(&self, crate_name: &str) -> AppResult<Vec<User>> {
+        if self.get_crate_summary(crate_name).await?.is_none() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+
+        let rows: Vec<UserRow> = sqlx::query_as(
+            "SELECT crate_owners.user_id AS id, \
+             COALESCE(users.login, '') AS login, \
+             COALESCE(users.given_name, '') AS given_name, \
+             COALESCE(users.family_name, '') AS family_name \
+             FROM crate_owners LEFT JOIN users ON users.id = crate_owners.user_id \
+             WHERE crate_owners.crate_name = $1 ORDER BY crate_owners.user_id",
+        )
+        .bind(crate_name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(UserRow::into_user).collect()
+    }
+
+    async fn add_owners(&self, crate_name: &str, user_ids: Vec<String>) -> AppResult<()> {
+        let user_ids = user_ids
+            .iter()
+            .map(|id| u32::from_str(id))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut tx = self.pool.begin().await?;
+        if get_crate_row(&mut tx, crate_name).await?.is_none() {
+            return Err(AppError::NonExistentPackageInfo(crate_name.to_string()));
+        }
+        for user_id in user_ids {
+            sqlx::query(
+                "INSERT INTO crate_owners (crate_name, user_id) VALUES ($1, $2) \
+                 ON CONFLICT DO NOTHING",
+            )
+            .bind(crate_name)
+            .bind(i64::from(user_id))
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn get_crate_summary(&self, crate_name: &str) -> AppResult<Option<CrateSummary>> {
+        let mut tx = self.pool.begin().await?;
+        let summary = match get_crate_row(&mut tx, crate_name).await? {
+            None => None,
+            Some(row) => Some(build_crate_summary(&mut tx, row).await?),
+        };
+        tx.commit().await?;
+
+        Ok(summary)
+    }
+
+    async fn get_all_crate_details(
+        &self,
+        filter: Option<String>,
+        limit: usize,
+        after: Option<String>,
+    ) -> AppResult<Vec<CrateSummary>> {
+        // the parameters are numbered as they are added, so that
+        // every combination of filters works on SQLite and PostgreSQL
+        let mut sql = "SELECT name, max_version, description FROM crates WHERE 1 = 1".to_string();
+        let mut parameter_count = 0;
+        let mut next_parameter = || {
+            parameter_count += 1;
+            format!("${}", parameter_count)
+        };
+        if filter.is_some() {
+            let length = next_parameter();
+            let prefix = next_parameter();
+            sql.push_str(&format!(" AND substr(name, 1, {}) = {}", length, prefix));
+        }
+        if after.is_some() {
+            sql.push_str(&format!(" AND name > {}", next_parameter()));
+        }
+        sql.push_str(&format!(" ORDER BY name LIMIT {}", next_parameter()));
+
+        let mut query = sqlx::query_as::<_, CrateRow>(&sql);
+        if let Some(prefix) = filter {
+            let length = i32::try_from(prefix.chars().count())
+                .map_err(|_| anyhow!("crate name filter is too long"))?;
+            query = query.bind(length).bind(prefix);
+        }
+        if let Some(after) = after {
+            query = query.bind(after);
+        }
+        let limit = i64::try_from(limit).map_err(|_| anyhow!("limit is too large"))?;
+        query = query.bind(limit);
+
+        let mut tx = self.pool.begin().await?;
+        let rows = query.fetch_all(&mut *tx).await?;
+        let mut crates = Vec::with_capacity(rows.len());
+        for row in rows {
+            crates.push(build_crate_summary(&mut tx, row).await?);
+        }
+        tx.commit().await?;
+
+        Ok(crates)
+    }
+
+    async fn get_crate_metadata(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<Metadata>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            "SELECT metadata FROM crate_versions WHERE crate_name = $1 AND version = $2",
+        )
+        .bind(crate_name)
+        .bind(version.to_string())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let metadata = match row {
+            None => None,
+            Some((metadata,)) => Some(serde_json::from_str(&metadata)?),
+        };
+
+        Ok(metadata)
+    }
+
+    async fn list_crate_versions(&self, crate_name: &str) -> AppResult<Vec<Version>> {
+        let rows: Vec<(String,)> =
+            sqlx::query_as("SELECT version FROM crate_versions WHERE crate_name = $1")
+                .bind(crate_name)
+                .fetch_all(&self.pool)
+                .await?;
+
+        let mut versions = rows
+            .iter()
+            .map(|(version,)| parse_version(version))
+            .collect::<AppResult<Vec<_>>>()?;
+        versions.sort();
+
+        Ok(versions)
+    }
+}
+
+async fn get_crate_row(
+    tx: &mut Transaction<'_, Any>,
+    crate_name: &str,
+) -> AppResult<Option<CrateRow>> {
+    let row = sqlx::query_as("SELECT name, max_version, description FROM crates WHERE name = $1")
+        .bind(crate_name)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+    Ok(row)
+}
+
+async fn get_owner_ids(tx: &mut Transaction<'_, Any>, crate_name: &str) -> AppResult<Vec<u32>> {
+    let rows: Vec<(i64,)> =
+        sqlx::query_as("SELECT user_id FROM crate_owners WHERE crate_name = $1 ORDER BY user_id")
+            .bind(crate_name)
+            .fetch_all(&mut **tx)
+            .await?;
+
+    rows.into_iter().map(|(id,)| to_user_id(id)).collect()
+}
+
+async fn build_crate_summary(
+    tx: &mut Transaction<'_, Any>,
+    row: CrateRow,
+) -> AppResult<CrateSummary> {
+    let owners = get_owner_ids(tx, &row.name).await?;
+
+    Ok(CrateSummary {
+        name: row.name,
+        owners,
+        max_version: parse_version(&row.max_version)?,
+        description: row.description,
+    })
+}
+
+/// Moves the crate's index file to its next revision.
+async fn bump_index_revision(tx: &mut Transaction<'_, Any>, crate_name: &str) -> AppResult<()> {
+    sqlx::query(
+        "UPDATE crates SET index_revision = index_revision + 1, index_last_modified = $1 \
+         WHERE name = $2",
+    )
+    .bind(Utc::now().to_rfc3339())
+    .bind(crate_name)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
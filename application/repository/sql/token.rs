@@ -0,0 +1,288 @@
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use hex::ToHex;
+use sqlx::FromRow;
+
+use crate::auth::{hash, PublicKey, TokenScopes};
+use crate::error::AppResult;
+use crate::models::token::Token;
+use crate::repository::base::TokenRepository;
+use crate::repository::sql::to_user_id;
+use crate::repository::SqlRepository;
+
+#[derive(FromRow)]
+struct TokenRow {
+    name: String,
+    user_id: i64,
+    token_id: String,
+    endpoint_scopes: Option<String>,
+    crate_scopes: Option<String>,
+    created_at: Option<String>,
+    expires_at: Option<String>,
+    last_used_at: Option<String>,
+    public_key: Option<String>,
+}
+
+impl TokenRow {
+    fn into_token(self) -> AppResult<Token> {
+        Ok(Token {
+            name: self.name,
+            user_id: to_user_id(self.user_id)?,
+            token_id: self.token_id,
+            scopes: TokenScopes {
+                endpoints: self
+                    .endpoint_scopes
+                    .as_deref()
+                    .map(serde_json::from_str)
+                    .transpose()?,
+                crates: self
+                    .crate_scopes
+                    .as_deref()
+                    .map(serde_json::from_str)
+                    .transpose()?,
+            },
+            created_at: parse_timestamp(self.created_at)?,
+            expires_at: parse_timestamp(self.expires_at)?,
+            last_used_at: parse_timestamp(self.last_used_at)?,
+            public_key: self.public_key,
+        })
+    }
+}
+
+const TOKEN_COLUMNS: &str = "name, user_id, token_id, endpoint_scopes, crate_scopes, \
+                             created_at, expires_at, last_used_at, public_key";
+
+#[async_trait::async_trait]
+impl TokenRepository for SqlRepository {
+    async fn store_auth_token(
+        &self,
+        token: &[u8],
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = Token::new(name, user_id, scopes, expires_at);
+        self.insert_token(token, &token_item).await?;
+
+        Ok(token_item)
+    }
+
+    async fn store_public_key(
+        &self,
+        public_key: &PublicKey,
+        name: String,
+        user_id: u32,
+        scopes: TokenScopes,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> AppResult<Token> {
+        let token_item = Token {
+            public_key: Some(public_key.to_string()),
+            ..Token::new(name, user_id, scopes, expires_at)
+        };
+        self.insert_token(public_key.id().as_bytes(), &token_item)
+            .await?;
+
+        Ok(token_item)
+    }
+
+    async fn delete_auth_token(&self, user_id: u32, token_id: String) -> AppResult<()> {
+        sqlx::query("DELETE FROM tokens WHERE user_id = $1 AND token_id = $2")
+            .bind(i64::from(user_id))
+            .bind(token_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_auth_tokens(&self, user_id: u32) -> AppResult<Vec<Token>> {
+        let query = format!("SELECT {} FROM tokens WHERE user_id = $1", TOKEN_COLUMNS);
+        let rows: Vec<TokenRow> = sqlx::query_as(&query)
+            .bind(i64::from(user_id))
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(TokenRow::into_token).collect()
+    }
+
+    async fn get_auth_token(&self, token: &[u8]) -> AppResult<Option<Token>> {
+        let query = format!("SELECT {} FROM tokens WHERE token_hash = $1", TOKEN_COLUMNS);
+        let row: Option<TokenRow> = sqlx::query_as(&query)
+            .bind(get_token_hash(token))
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(TokenRow::into_token).transpose()
+    }
+
+    async fn record_token_use(&self, token: &[u8], used_at: DateTime<Utc>) -> AppResult<()> {
+        sqlx::query("UPDATE tokens SET last_used_at = $1 WHERE token_hash = $2")
+            .bind(used_at.to_rfc3339())
+            .bind(get_token_hash(token))
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn claim_signed_token(&self, token: &[u8], expires_at: DateTime<Utc>) -> AppResult<bool> {
+        // timestamps are all in UTC and formatted the same way, so they compare as strings
+        sqlx::query("DELETE FROM signed_tokens WHERE expires_at <= $1")
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        let result = sqlx::query(
+            "INSERT INTO signed_tokens (token_hash, expires_at) VALUES ($1, $2) \
+             ON CONFLICT (token_hash) DO NOTHING",
+        )
+        .bind(get_token_hash(token))
+        .bind(expires_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+}
+
+impl SqlRepository {
+    /// Stores the token under the hash of `token`.
+    async fn insert_token(&self, token: &[u8], token_item: &Token) -> AppResult<()> {
+        let endpoint_scopes = token_item
+            .scopes
+            .endpoints
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        let crate_scopes = token_item
+            .scopes
+            .crates
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        sqlx::query(
+            "INSERT INTO tokens (token_hash, token_id, name, user_id, endpoint_scopes, crate_scopes, \
+             created_at, expires_at, public_key) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(get_token_hash(token))
+        .bind(&token_item.token_id)
+        .bind(&token_item.name)
+        .bind(i64::from(token_item.user_id))
+        .bind(endpoint_scopes)
+        .bind(crate_scopes)
+        .bind(token_item.created_at.map(|t| t.to_rfc3339()))
+        .bind(token_item.expires_at.map(|t| t.to_rfc3339()))
+        .bind(&token_item.public_key)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn parse_timestamp(timestamp: Option<String>) -> AppResult<Option<DateTime<Utc>>> {
+    timestamp
+        .map(|t| {
+            DateTime::parse_from_rfc3339(&t)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|_| anyhow!("invalid timestamp {} in database", t).into())
+        })
+        .transpose()
+}
+
+fn get_token_hash(token: &[u8]) -> String {
+    hash(token).encode_hex()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::EndpointScope;
+    use crate::repository::sql::tests::build_repository;
+    use chrono::Duration;
+    use p384::ecdsa::SigningKey;
+    use rand::rngs::OsRng;
+
+    #[tokio::test]
+    async fn test_token_scopes_are_stored() {
+        let repository = build_repository().await;
+        let scopes = TokenScopes {
+            endpoints: Some(vec![EndpointScope::PublishUpdate, EndpointScope::Yank]),
+            crates: Some(vec!["raktar-*".parse().unwrap()]),
+        };
+
+        repository
+            .store_auth_token(b"scoped", "ci".to_string(), 1, scopes.clone(), None)
+            .await
+            .unwrap();
+        repository
+            .store_auth_token(
+                b"unscoped",
+                "local".to_string(),
+                1,
+                TokenScopes::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let scoped = repository.get_auth_token(b"scoped").await.unwrap().unwrap();
+        assert_eq!(scoped.scopes, scopes);
+        let unscoped = repository
+            .get_auth_token(b"unscoped")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(unscoped.scopes, TokenScopes::default());
+    }
+
+    #[tokio::test]
+    async fn test_public_keys_are_stored() {
+        let repository = build_repository().await;
+        let public_key = PublicKey::from(&SigningKey::random(&mut OsRng));
+
+        repository
+            .store_public_key(
+                &public_key,
+                "laptop".to_string(),
+                1,
+                TokenScopes::default(),
+                None,
+            )
+            .await
+            .unwrap();
+
+        let token = repository
+            .get_auth_token(public_key.id().as_bytes())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(token.public_key, Some(public_key.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_signed_tokens_are_claimed_once() {
+        let repository = build_repository().await;
+        let expires_at = Utc::now() + Duration::minutes(5);
+
+        assert!(repository
+            .claim_signed_token(b"signed", expires_at)
+            .await
+            .unwrap());
+        assert!(!repository
+            .claim_signed_token(b"signed", expires_at)
+            .await
+            .unwrap());
+
+        // expired claims are forgotten
+        let expired = Utc::now() - Duration::minutes(1);
+        assert!(repository
+            .claim_signed_token(b"expired", expired)
+            .await
+            .unwrap());
+        assert!(repository
+            .claim_signed_token(b"expired", expires_at)
+            .await
+            .unwrap());
+    }
+}
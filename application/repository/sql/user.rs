@@ -0,0 +1,149 @@
+use sqlx::FromRow;
+use tracing::info;
+
+use crate::error::AppResult;
+use crate::models::user::{CognitoUserData, User, UserId};
+use crate::repository::base::UserRepository;
+use crate::repository::sql::to_user_id;
+use crate::repository::SqlRepository;
+
+#[derive(FromRow)]
+pub struct UserRow {
+    id: i64,
+    login: String,
+    given_name: String,
+    family_name: String,
+}
+
+impl UserRow {
+    pub fn into_user(self) -> AppResult<User> {
+        Ok(User {
+            id: to_user_id(self.id)?,
+            login: self.login,
+            given_name: self.given_name,
+            family_name: self.family_name,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl UserRepository for SqlRepository {
+    async fn update_or_create_user(&self, user_data: CognitoUserData) -> AppResult<User> {
+        let mut tx = self.pool.begin().await?;
+        let existing_user: Option<UserRow> =
+            sqlx::query_as("SELECT id, login, given_name, family_name FROM users WHERE login = $1")
+                .bind(&user_data.login)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let user = match existing_user {
+            None => {
+                info!("user not found, creating new user");
+                let (max_id,): (i64,) = sqlx::query_as("SELECT COALESCE(MAX(id), 0) FROM users")
+                    .fetch_one(&mut *tx)
+                    .await?;
+                let next_id = to_user_id(max_id + 1)?;
+                info!("next available ID is {}", next_id);
+
+                let user = user_data.into_user(next_id);
+                sqlx::query(
+                    "INSERT INTO users (id, login, given_name, family_name) VALUES ($1, $2, $3, $4)",
+                )
+                .bind(i64::from(user.id))
+                .bind(&user.login)
+                .bind(&user.given_name)
+                .bind(&user.family_name)
+                .execute(&mut *tx)
+                .await?;
+                user
+            }
+            Some(row) => {
+                let user = row.into_user()?;
+
+                // if the existing user data is out of sync, update it
+                let existing_user_data: CognitoUserData = user.clone().into();
+                if existing_user_data != user_data {
+                    sqlx::query("UPDATE users SET given_name = $1, family_name = $2 WHERE id = $3")
+                        .bind(&user_data.given_name)
+                        .bind(&user_data.family_name)
+                        .bind(i64::from(user.id))
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                user
+            }
+        };
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    async fn get_user_by_id(&self, user_id: UserId) -> AppResult<Option<User>> {
+        let row: Option<UserRow> =
+            sqlx::query_as("SELECT id, login, given_name, family_name FROM users WHERE id = $1")
+                .bind(i64::from(user_id))
+                .fetch_optional(&self.pool)
+                .await?;
+
+        row.map(UserRow::into_user).transpose()
+    }
+
+    async fn get_users(&self) -> AppResult<Vec<User>> {
+        let rows: Vec<UserRow> =
+            sqlx::query_as("SELECT id, login, given_name, family_name FROM users ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.into_iter().map(UserRow::into_user).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::sql::tests::build_repository;
+
+    fn user_data(login: &str, given_name: &str) -> CognitoUserData {
+        CognitoUserData {
+            login: login.to_string(),
+            given_name: given_name.to_string(),
+            family_name: "Doe".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_users_get_consecutive_ids() {
+        let repository = build_repository().await;
+
+        let jane = repository
+            .update_or_create_user(user_data("jane", "Jane"))
+            .await
+            .unwrap();
+        let john = repository
+            .update_or_create_user(user_data("john", "John"))
+            .await
+            .unwrap();
+
+        assert_eq!(jane.id, 1);
+        assert_eq!(john.id, 2);
+        assert_eq!(repository.get_users().await.unwrap(), vec![jane, john]);
+    }
+
+    #[tokio::test]
+    async fn test_existing_user_is_updated() {
+        let repository = build_repository().await;
+        let user = repository
+            .update_or_create_user(user_data("jane", "Jane"))
+            .await
+            .unwrap();
+
+        repository
+            .update_or_create_user(user_data("jane", "Janet"))
+            .await
+            .unwrap();
+
+        let updated_user = repository.get_user_by_id(user.id).await.unwrap().unwrap();
+        assert_eq!(updated_user.id, user.id);
+        assert_eq!(updated_user.given_name, "Janet");
+    }
+}
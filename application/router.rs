@@ -1,68 +1,143 @@
-use crate::auth::token_authenticator;
+use crate::auth::jwt::JwtVerifier;
+use crate::auth::oidc::{DynIdentityProvider, OidcProvider};
+use crate::auth::session::SessionKeys;
+use crate::auth::{login, login_callback, read_authenticator, token_authenticator, LoginState};
 use crate::cargo_api::config::get_config_json;
+use crate::cargo_api::download::counter::DownloadCounter;
 use crate::cargo_api::download::download_crate;
-use crate::cargo_api::index::{
-    get_info_for_long_name_crate, get_info_for_short_name_crate, get_info_for_three_letter_crate,
-};
+use crate::cargo_api::index::get_index_file_handler;
 use crate::cargo_api::me::redirect_for_token;
 use crate::cargo_api::owners::{add_owners, list_owners};
-use crate::cargo_api::publish::publish_crate_handler;
+use crate::cargo_api::publish::{publish_crate_handler, MAX_PUBLISH_BODY_SIZE};
 use crate::cargo_api::unyank::unyank;
 use crate::cargo_api::yank::yank;
+use crate::config::RegistryConfig;
 use crate::graphql::handler::{graphiql, graphql_handler};
 use crate::graphql::schema::build_schema;
 use crate::repository::DynRepository;
 use crate::storage::DynCrateStorage;
+use axum::extract::DefaultBodyLimit;
 use axum::routing::{delete, get, put, Router};
 use axum::Extension;
+use std::sync::Arc;
 
-pub type AppState = (DynRepository, DynCrateStorage);
+#[derive(Clone)]
+pub struct AppState {
+    pub repository: DynRepository,
+    pub storage: DynCrateStorage,
+    pub config: Arc<RegistryConfig>,
+    pub downloads: DownloadCounter,
+}
 
-pub fn build_router(repository: DynRepository, storage: DynCrateStorage) -> Router {
-    let core_router = build_core_router(repository.clone());
-    let graphql_router = build_graphql_router(repository.clone());
-    let state = (repository, storage);
+pub fn build_router(
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    config: RegistryConfig,
+) -> Router {
+    let identity_provider = config
+        .oidc
+        .clone()
+        .map(|oidc| Arc::new(OidcProvider::new(oidc)) as DynIdentityProvider);
 
-    Router::new()
+    build_router_with_identity_provider(repository, storage, config, identity_provider)
+}
+
+/// Builds the router with the provider users log in with, which is only used when
+/// the configuration has OIDC set up.
+pub fn build_router_with_identity_provider(
+    repository: DynRepository,
+    storage: DynCrateStorage,
+    config: RegistryConfig,
+    identity_provider: Option<DynIdentityProvider>,
+) -> Router {
+    let jwt_verifier = config.jwt.clone().map(JwtVerifier::new);
+    let login_state = config
+        .oidc
+        .as_ref()
+        .zip(identity_provider)
+        .map(|(oidc, provider)| LoginState {
+            repository: repository.clone(),
+            provider,
+            sessions: SessionKeys::new(&config.api_url, oidc.session_secret.as_bytes()),
+            frontend_url: oidc.frontend_url.clone(),
+        });
+    let sessions = login_state.as_ref().map(|state| state.sessions.clone());
+    let graphql_router = build_graphql_router(repository.clone(), jwt_verifier, sessions);
+    let downloads = DownloadCounter::start(repository.clone());
+    let state = AppState {
+        repository,
+        storage,
+        config: Arc::new(config),
+        downloads,
+    };
+    let core_router = build_core_router(state.clone());
+
+    let mut router = Router::new()
         .route("/config.json", get(get_config_json))
         .route("/me", get(redirect_for_token))
         .nest("/", core_router)
-        .nest("/gql", graphql_router)
-        .with_state(state)
+        .nest("/gql", graphql_router);
+    if let Some(login_state) = login_state {
+        router = router.nest("/auth", build_login_router(login_state));
+    }
+
+    router.with_state(state)
 }
 
-fn build_core_router(repository: DynRepository) -> Router<AppState> {
-    Router::new()
-        .route("/api/v1/crates/new", put(publish_crate_handler))
-        .route(
-            "/api/v1/crates/:crate_name/owners",
-            get(list_owners).put(add_owners),
-        )
-        .route("/api/v1/crates/:crate_name/:version/yank", delete(yank))
-        .route("/api/v1/crates/:crate_name/:version/unyank", put(unyank))
+fn build_core_router(state: AppState) -> Router<AppState> {
+    // the index and the downloads can be configured to allow anonymous access
+    let read_router = Router::new()
         .route(
             "/api/v1/crates/:crate_name/:version/download",
             get(download_crate),
         )
-        .route("/1/:crate_name", get(get_info_for_short_name_crate))
-        .route("/2/:crate_name", get(get_info_for_short_name_crate))
+        .route("/1/:crate_name", get(get_index_file_handler))
+        .route("/2/:crate_name", get(get_index_file_handler))
+        .route("/3/:first_letter/:crate_name", get(get_index_file_handler))
         .route(
-            "/3/:first_letter/:crate_name",
-            get(get_info_for_three_letter_crate),
+            "/:first_two/:second_two/:crate_name",
+            get(get_index_file_handler),
         )
+        .route_layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            read_authenticator,
+        ));
+
+    let write_router = Router::new()
         .route(
-            "/:first_two/:second_two/:crate_name",
-            get(get_info_for_long_name_crate),
+            "/api/v1/crates/new",
+            put(publish_crate_handler).layer(DefaultBodyLimit::max(MAX_PUBLISH_BODY_SIZE)),
+        )
+        .route(
+            "/api/v1/crates/:crate_name/owners",
+            get(list_owners).put(add_owners),
         )
-        .layer(axum::middleware::from_fn_with_state(
-            repository,
+        .route("/api/v1/crates/:crate_name/:version/yank", delete(yank))
+        .route("/api/v1/crates/:crate_name/:version/unyank", put(unyank))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
             token_authenticator,
-        ))
+        ));
+
+    read_router.merge(write_router)
 }
 
-fn build_graphql_router(repository: DynRepository) -> Router<AppState> {
+fn build_graphql_router(
+    repository: DynRepository,
+    jwt_verifier: Option<JwtVerifier>,
+    sessions: Option<SessionKeys>,
+) -> Router<AppState> {
     let schema = build_schema(repository);
     Router::new()
         .route("/", get(graphiql).post(graphql_handler))
         .layer(Extension(schema))
+        .layer(Extension(jwt_verifier))
+        .layer(Extension(sessions))
+}
+
+fn build_login_router(login_state: LoginState) -> Router<AppState> {
+    Router::new()
+        .route("/login", get(login))
+        .route("/callback", get(login_callback))
+        .with_state(login_state)
 }
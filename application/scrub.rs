@@ -0,0 +1,247 @@
+//! Reconciles the index in the repository with the crates in storage.
+//!
+//! Every version in the index is read back from storage and checked against the checksum
+//! in its index entry, and every stored crate is checked to have a version in the index.
+//! Publishing is not paused while this runs, and a publish stores its crate before adding
+//! it to the index, so a crate that's not in the index may just not be indexed yet. Only
+//! crates that were stored longer ago than a grace period are taken to be orphans, which
+//! leaves publishes that are still in progress alone.
+use futures::TryStreamExt;
+use hex::ToHex;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{AppError, AppResult};
+use crate::models::index::PackageInfo;
+use crate::repository::DynRepository;
+use crate::storage::{DynCrateStorage, StoredCrate};
+
+const CRATES_PAGE_SIZE: usize = 100;
+/// How long a stored crate can go without being in the index before it's an orphan,
+/// which is far longer than any publish takes.
+pub const ORPHAN_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// A crate is stored, but its version is not in the index.
+    OrphanedCrate {
+        crate_name: String,
+        version: Version,
+    },
+    /// A version is in the index, but its crate is not stored.
+    MissingCrate {
+        crate_name: String,
+        version: Version,
+    },
+    /// The stored crate doesn't match the checksum in the index.
+    ChecksumMismatch {
+        crate_name: String,
+        version: Version,
+        expected: String,
+        actual: String,
+    },
+    /// The stored crate couldn't be read to the end.
+    UnreadableCrate {
+        crate_name: String,
+        version: Version,
+        reason: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::OrphanedCrate {
+                crate_name,
+                version,
+            } => write!(
+                f,
+                "{} {} is stored but not in the index",
+                crate_name, version
+            ),
+            Problem::MissingCrate {
+                crate_name,
+                version,
+            } => write!(
+                f,
+                "{} {} is in the index but not stored",
+                crate_name, version
+            ),
+            Problem::ChecksumMismatch {
+                crate_name,
+                version,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{} {} has checksum {} instead of {}",
+                crate_name, version, actual, expected
+            ),
+            Problem::UnreadableCrate {
+                crate_name,
+                version,
+                reason,
+            } => write!(f, "{} {} can't be read: {}", crate_name, version, reason),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScrubReport {
+    /// The number of versions in the index.
+    pub checked_versions: usize,
+    pub problems: Vec<Problem>,
+    /// What was done to repair the problems, if repairing was asked for.
+    pub repairs: Vec<String>,
+}
+
+/// Checks every version in the index against storage, and the other way around.
+///
+/// Crates that aren't in the index are only orphans once they've been stored for longer
+/// than `orphan_grace_period`, see [`ORPHAN_GRACE_PERIOD`].
+///
+/// With `repair`, orphaned crates are deleted from storage, and versions whose crate is
+/// missing or broken are yanked, so that Cargo stops picking them for new lockfiles.
+pub async fn scrub(
+    repository: &DynRepository,
+    storage: &DynCrateStorage,
+    repair: bool,
+    orphan_grace_period: Duration,
+) -> AppResult<ScrubReport> {
+    let mut report = ScrubReport::default();
+
+    // the index is listed before the storage, so that a crate published in between
+    // looks orphaned rather than missing, which is checked again before repairing
+    let index = list_index(repository).await?;
+    let stored_crates = storage.list_crates().await?;
+    report.checked_versions = index.len();
+
+    let mut indexed = HashSet::new();
+    for info in index {
+        indexed.insert((info.name.clone(), info.vers.clone()));
+        if let Some(problem) = check_stored_crate(storage, &info).await? {
+            if repair && !info.yanked {
+                repository.set_yanked(&info.name, &info.vers, true).await?;
+                report
+                    .repairs
+                    .push(format!("yanked {} {}", info.name, info.vers));
+            }
+            report.problems.push(problem);
+        }
+    }
+
+    for stored in stored_crates {
+        if indexed.contains(&(stored.name.clone(), stored.version.clone()))
+            || !is_past_grace_period(&stored, orphan_grace_period)
+        {
+            continue;
+        }
+
+        let published_since = repository
+            .get_package_version_info(&stored.name, &stored.version)
+            .await?
+            .is_some();
+        if published_since {
+            continue;
+        }
+
+        if repair {
+            storage
+                .delete_crate(&stored.name, stored.version.clone())
+                .await?;
+            report
+                .repairs
+                .push(format!("deleted {} {}", stored.name, stored.version));
+        }
+        report.problems.push(Problem::OrphanedCrate {
+            crate_name: stored.name,
+            version: stored.version,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Whether the crate was stored long enough ago that it should have been indexed by now.
+fn is_past_grace_period(stored: &StoredCrate, grace_period: Duration) -> bool {
+    // a time in the future, from clocks that are out of sync, counts as just stored
+    let age = SystemTime::now()
+        .duration_since(stored.last_modified)
+        .unwrap_or_default();
+
+    age >= grace_period
+}
+
+/// Gets the index entries of every version of every crate.
+async fn list_index(repository: &DynRepository) -> AppResult<Vec<PackageInfo>> {
+    let mut infos = Vec::new();
+    let mut after = None;
+    loop {
+        let crates = repository
+            .get_all_crate_details(None, CRATES_PAGE_SIZE, after)
+            .await?;
+        let Some(last) = crates.last() else {
+            break;
+        };
+        after = Some(last.name.clone());
+
+        for summary in crates {
+            for version in repository.list_crate_versions(&summary.name).await? {
+                if let Some(info) = repository
+                    .get_package_version_info(&summary.name, &version)
+                    .await?
+                {
+                    infos.push(info);
+                }
+            }
+        }
+    }
+
+    Ok(infos)
+}
+
+/// Reads the stored crate back, and checks it against the checksum in the index.
+async fn check_stored_crate(
+    storage: &DynCrateStorage,
+    info: &PackageInfo,
+) -> AppResult<Option<Problem>> {
+    let body = match storage.get_crate(&info.name, info.vers.clone()).await {
+        Ok(body) => body,
+        Err(AppError::NonExistentCrateVersion { .. }) => {
+            return Ok(Some(Problem::MissingCrate {
+                crate_name: info.name.clone(),
+                version: info.vers.clone(),
+            }));
+        }
+        Err(err) => return Err(err),
+    };
+
+    let hasher = body
+        .stream
+        .try_fold(Sha256::new(), |mut hasher, chunk| async move {
+            hasher.update(&chunk);
+            Ok(hasher)
+        })
+        .await;
+    let problem = match hasher {
+        Err(err) => Some(Problem::UnreadableCrate {
+            crate_name: info.name.clone(),
+            version: info.vers.clone(),
+            reason: err.to_string(),
+        }),
+        Ok(hasher) => {
+            let checksum: String = hasher.finalize().encode_hex();
+            (checksum != info.cksum).then(|| Problem::ChecksumMismatch {
+                crate_name: info.name.clone(),
+                version: info.vers.clone(),
+                expected: info.cksum.clone(),
+                actual: checksum,
+            })
+        }
+    };
+
+    Ok(problem)
+}
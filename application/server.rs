@@ -0,0 +1,82 @@
+//! The standalone HTTP(S) server, for running the registry outside of Lambda.
+mod config;
+
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use axum_server::Handle;
+use std::time::Duration;
+use tower_http::cors::{Any, CorsLayer};
+use tracing::info;
+
+pub use config::{RegistrySettings, RepositoryBackend, ServerConfig, StorageBackend, TlsConfig};
+
+/// Serves the app until the process receives Ctrl+C or `SIGTERM`.
+///
+/// Once a signal arrives, no new connections are accepted, and in-flight requests
+/// get `shutdown_timeout_seconds` to finish.
+pub async fn serve(app: Router, config: &ServerConfig) -> anyhow::Result<()> {
+    let app = if config.cors_allow_any_origin {
+        let cors_layer = CorsLayer::new()
+            .allow_methods([http::Method::GET, http::Method::POST])
+            .allow_headers(Any)
+            .allow_origin(Any);
+        app.layer(cors_layer)
+    } else {
+        app
+    };
+
+    let handle = Handle::new();
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_seconds);
+    tokio::spawn(shutdown_on_signal(handle.clone(), shutdown_timeout));
+
+    let addr = config.listen_address;
+    let service = app.into_make_service();
+    match &config.tls {
+        None => {
+            info!("listening on http://{}", addr);
+            axum_server::bind(addr)
+                .handle(handle)
+                .serve(service)
+                .await?;
+        }
+        Some(tls) => {
+            let rustls_config = RustlsConfig::from_pem_file(&tls.certificate, &tls.private_key)
+                .await
+                .map_err(|err| anyhow::anyhow!("failed to load TLS certificate: {}", err))?;
+            info!("listening on https://{}", addr);
+            axum_server::bind_rustls(addr, rustls_config)
+                .handle(handle)
+                .serve(service)
+                .await?;
+        }
+    }
+
+    info!("server stopped");
+    Ok(())
+}
+
+async fn shutdown_on_signal(handle: Handle, timeout: Duration) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    info!("shutting down, waiting for in-flight requests to finish");
+    handle.graceful_shutdown(Some(timeout));
+}
@@ -0,0 +1,443 @@
+use anyhow::{anyhow, Context};
+use clap::Args;
+use serde::Deserialize;
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::RegistryConfig;
+use crate::repository::{DynRepository, DynamoDBRepository, InMemoryRepository, SqlRepository};
+use crate::storage::{DynCrateStorage, FilesystemStorage, S3Storage};
+
+/// Configuration of the standalone server, usually loaded from a TOML file such as
+///
+/// ```toml
+/// listen_address = "0.0.0.0:443"
+///
+/// [tls]
+/// certificate = "/etc/raktar/cert.pem"
+/// private_key = "/etc/raktar/key.pem"
+///
+/// [repository]
+/// type = "sql"
+/// database_url = "sqlite:///var/lib/raktar/raktar.db?mode=rwc"
+///
+/// [storage]
+/// type = "filesystem"
+/// root = "/var/lib/raktar"
+///
+/// [registry]
+/// domain_name = "raktar.example.com"
+/// anonymous_read_crates = ["public-*"]
+/// ```
+///
+/// Backends that are left out are picked based on the environment,
+/// in the same way as when the registry runs on Lambda.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default = "default_listen_address")]
+    pub listen_address: SocketAddr,
+    /// Serves HTTPS instead of HTTP when set.
+    pub tls: Option<TlsConfig>,
+    /// How long in-flight requests are given to finish once shutdown starts.
+    #[serde(default = "default_shutdown_timeout_seconds")]
+    pub shutdown_timeout_seconds: u64,
+    /// Allows requests from any origin, which is handy when developing the frontend.
+    #[serde(default)]
+    pub cors_allow_any_origin: bool,
+    #[serde(default = "RepositoryBackend::from_env")]
+    pub repository: RepositoryBackend,
+    #[serde(default = "StorageBackend::from_env")]
+    pub storage: StorageBackend,
+    /// The registry settings, which take precedence over the environment.
+    #[serde(default)]
+    pub registry: RegistrySettings,
+}
+
+/// The settings of the registry, each of which can also be set with the environment
+/// variable of the same name in upper case, see [`RegistryConfig::from_vars`].
+///
+/// They can be set in the `[registry]` table of the config file, or with flags,
+/// except for the secrets, which shouldn't show up in the list of processes.
+#[derive(Args, Clone, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RegistrySettings {
+    /// The domain the registry is served from, which the URLs default to.
+    #[arg(long)]
+    pub domain_name: Option<String>,
+    /// The URL template Cargo downloads crates from, which may contain markers such as `{crate}`.
+    #[arg(long)]
+    pub download_url_template: Option<String>,
+    /// The base URL of the web API.
+    #[arg(long)]
+    pub api_url: Option<String>,
+    /// The URL Cargo is configured with for the index.
+    #[arg(long)]
+    pub index_url: Option<String>,
+    /// Whether Cargo has to send a token for every request.
+    #[arg(long)]
+    pub auth_required: Option<bool>,
+    /// Lets every crate be read without a token.
+    #[arg(long)]
+    pub anonymous_read: Option<bool>,
+    /// The crate patterns that can be read without a token, such as `public-*`.
+    #[arg(long, value_delimiter = ',')]
+    pub anonymous_read_crates: Option<Vec<String>>,
+    /// The issuer of the web frontend's tokens.
+    #[arg(long)]
+    pub jwt_issuer: Option<String>,
+    /// The audience of the web frontend's tokens.
+    #[arg(long)]
+    pub jwt_audience: Option<String>,
+    /// Path to the key set the web frontend's tokens are verified with.
+    #[arg(long)]
+    pub jwks_file: Option<PathBuf>,
+    /// The issuer of the OIDC provider users log in with.
+    #[arg(long)]
+    pub oidc_issuer: Option<String>,
+    /// The client ID of the registry at the OIDC provider.
+    #[arg(long)]
+    pub oidc_client_id: Option<String>,
+    #[arg(skip)]
+    pub oidc_client_secret: Option<String>,
+    /// Where the OIDC provider sends users back to.
+    #[arg(long)]
+    pub oidc_redirect_url: Option<String>,
+    /// Where users end up once they're logged in.
+    #[arg(long)]
+    pub frontend_url: Option<String>,
+    #[arg(skip)]
+    pub session_secret: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    /// Path to the PEM encoded certificate chain.
+    pub certificate: PathBuf,
+    /// Path to the PEM encoded private key.
+    pub private_key: PathBuf,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum RepositoryBackend {
+    /// Uses the table in `table_name`, or in `TABLE_NAME` if it's not set.
+    DynamoDB {
+        table_name: Option<String>,
+    },
+    Sql {
+        database_url: String,
+    },
+    /// Keeps everything in memory, so it's lost when the server stops.
+    Memory,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum StorageBackend {
+    /// Configured from the environment, see [`S3Storage::new`].
+    S3,
+    Filesystem {
+        root: PathBuf,
+    },
+}
+
+impl ServerConfig {
+    pub fn from_env() -> Self {
+        Self {
+            listen_address: default_listen_address(),
+            tls: None,
+            shutdown_timeout_seconds: default_shutdown_timeout_seconds(),
+            cors_allow_any_origin: false,
+            repository: RepositoryBackend::from_env(),
+            storage: StorageBackend::from_env(),
+            registry: RegistrySettings::default(),
+        }
+    }
+
+    /// Builds the registry configuration from the registry settings, falling back to
+    /// the environment for the ones that aren't set.
+    pub fn registry_config(&self) -> anyhow::Result<RegistryConfig> {
+        self.registry_config_from(|key| std::env::var(key).ok())
+    }
+
+    fn registry_config_from(
+        &self,
+        get_env_var: impl Fn(&str) -> Option<String>,
+    ) -> anyhow::Result<RegistryConfig> {
+        RegistryConfig::from_vars(|key| self.registry.get(key).or_else(|| get_env_var(key)))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        toml::from_str(contents).map_err(|err| anyhow!("invalid config file: {}", err))
+    }
+}
+
+impl RegistrySettings {
+    /// Applies the settings on top of `base`, keeping the ones of `base` that aren't set.
+    pub fn over(self, base: Self) -> Self {
+        Self {
+            domain_name: self.domain_name.or(base.domain_name),
+            download_url_template: self.download_url_template.or(base.download_url_template),
+            api_url: self.api_url.or(base.api_url),
+            index_url: self.index_url.or(base.index_url),
+            auth_required: self.auth_required.or(base.auth_required),
+            anonymous_read: self.anonymous_read.or(base.anonymous_read),
+            anonymous_read_crates: self.anonymous_read_crates.or(base.anonymous_read_crates),
+            jwt_issuer: self.jwt_issuer.or(base.jwt_issuer),
+            jwt_audience: self.jwt_audience.or(base.jwt_audience),
+            jwks_file: self.jwks_file.or(base.jwks_file),
+            oidc_issuer: self.oidc_issuer.or(base.oidc_issuer),
+            oidc_client_id: self.oidc_client_id.or(base.oidc_client_id),
+            oidc_client_secret: self.oidc_client_secret.or(base.oidc_client_secret),
+            oidc_redirect_url: self.oidc_redirect_url.or(base.oidc_redirect_url),
+            frontend_url: self.frontend_url.or(base.frontend_url),
+            session_secret: self.session_secret.or(base.session_secret),
+        }
+    }
+
+    /// Gets the setting of the environment variable with the given name.
+    fn get(&self, key: &str) -> Option<String> {
+        match key {
+            "DOMAIN_NAME" => self.domain_name.clone(),
+            "DOWNLOAD_URL_TEMPLATE" => self.download_url_template.clone(),
+            "API_URL" => self.api_url.clone(),
+            "INDEX_URL" => self.index_url.clone(),
+            "AUTH_REQUIRED" => self.auth_required.map(|flag| flag.to_string()),
+            "ANONYMOUS_READ" => self.anonymous_read.map(|flag| flag.to_string()),
+            "ANONYMOUS_READ_CRATES" => self
+                .anonymous_read_crates
+                .as_ref()
+                .map(|patterns| patterns.join(",")),
+            "JWT_ISSUER" => self.jwt_issuer.clone(),
+            "JWT_AUDIENCE" => self.jwt_audience.clone(),
+            "JWKS_FILE" => self
+                .jwks_file
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            "OIDC_ISSUER" => self.oidc_issuer.clone(),
+            "OIDC_CLIENT_ID" => self.oidc_client_id.clone(),
+            "OIDC_CLIENT_SECRET" => self.oidc_client_secret.clone(),
+            "OIDC_REDIRECT_URL" => self.oidc_redirect_url.clone(),
+            "FRONTEND_URL" => self.frontend_url.clone(),
+            "SESSION_SECRET" => self.session_secret.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// Leaves the secrets out, so that the settings can be logged.
+impl fmt::Debug for RegistrySettings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let redacted = |secret: &Option<String>| secret.as_ref().map(|_| "<redacted>");
+        f.debug_struct("RegistrySettings")
+            .field("domain_name", &self.domain_name)
+            .field("download_url_template", &self.download_url_template)
+            .field("api_url", &self.api_url)
+            .field("index_url", &self.index_url)
+            .field("auth_required", &self.auth_required)
+            .field("anonymous_read", &self.anonymous_read)
+            .field("anonymous_read_crates", &self.anonymous_read_crates)
+            .field("jwt_issuer", &self.jwt_issuer)
+            .field("jwt_audience", &self.jwt_audience)
+            .field("jwks_file", &self.jwks_file)
+            .field("oidc_issuer", &self.oidc_issuer)
+            .field("oidc_client_id", &self.oidc_client_id)
+            .field("oidc_client_secret", &redacted(&self.oidc_client_secret))
+            .field("oidc_redirect_url", &self.oidc_redirect_url)
+            .field("frontend_url", &self.frontend_url)
+            .field("session_secret", &redacted(&self.session_secret))
+            .finish()
+    }
+}
+
+impl RepositoryBackend {
+    /// Uses the SQL database at `DATABASE_URL` when it's set, and DynamoDB otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("DATABASE_URL") {
+            Ok(database_url) => Self::Sql { database_url },
+            Err(_) => Self::DynamoDB { table_name: None },
+        }
+    }
+
+    pub async fn build(&self) -> anyhow::Result<DynRepository> {
+        let repository = match self {
+            Self::DynamoDB { table_name } => {
+                let aws_config = aws_config::from_env().load().await;
+                let db_client = aws_sdk_dynamodb::Client::new(&aws_config);
+                let table_name = match table_name {
+                    Some(table_name) => table_name.clone(),
+                    None => std::env::var("TABLE_NAME")
+                        .map_err(|_| anyhow!("TABLE_NAME is not set in environment"))?,
+                };
+                Arc::new(DynamoDBRepository::new(db_client, table_name)) as DynRepository
+            }
+            Self::Sql { database_url } => {
+                Arc::new(SqlRepository::connect(database_url).await?) as DynRepository
+            }
+            Self::Memory => Arc::new(InMemoryRepository::new()) as DynRepository,
+        };
+
+        Ok(repository)
+    }
+}
+
+impl StorageBackend {
+    /// Stores crates on disk when `CRATES_DIRECTORY` is set, and in S3 otherwise.
+    pub fn from_env() -> Self {
+        match std::env::var("CRATES_DIRECTORY") {
+            Ok(root) => Self::Filesystem { root: root.into() },
+            Err(_) => Self::S3,
+        }
+    }
+
+    pub async fn build(&self) -> DynCrateStorage {
+        match self {
+            Self::S3 => Arc::new(S3Storage::new().await) as DynCrateStorage,
+            Self::Filesystem { root } => {
+                Arc::new(FilesystemStorage::new(root.clone())) as DynCrateStorage
+            }
+        }
+    }
+}
+
+fn default_listen_address() -> SocketAddr {
+    SocketAddr::from(([0, 0, 0, 0], 3026))
+}
+
+fn default_shutdown_timeout_seconds() -> u64 {
+    30
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_config() {
+        let config = ServerConfig::parse(
+            r#"
+            listen_address = "127.0.0.1:8443"
+            shutdown_timeout_seconds = 5
+            cors_allow_any_origin = true
+
+            [tls]
+            certificate = "cert.pem"
+            private_key = "key.pem"
+
+            [repository]
+            type = "sql"
+            database_url = "sqlite://raktar.db"
+
+            [storage]
+            type = "filesystem"
+            root = "/var/lib/raktar"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.listen_address, "127.0.0.1:8443".parse().unwrap());
+        assert_eq!(config.shutdown_timeout_seconds, 5);
+        assert!(config.cors_allow_any_origin);
+        assert_eq!(
+            config.tls,
+            Some(TlsConfig {
+                certificate: "cert.pem".into(),
+                private_key: "key.pem".into(),
+            })
+        );
+        assert_eq!(
+            config.repository,
+            RepositoryBackend::Sql {
+                database_url: "sqlite://raktar.db".to_string()
+            }
+        );
+        assert_eq!(
+            config.storage,
+            StorageBackend::Filesystem {
+                root: "/var/lib/raktar".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = ServerConfig::parse("[repository]\ntype = \"memory\"").unwrap();
+
+        assert_eq!(config.listen_address, default_listen_address());
+        assert_eq!(config.tls, None);
+        assert_eq!(config.shutdown_timeout_seconds, 30);
+        assert!(!config.cors_allow_any_origin);
+        assert_eq!(config.repository, RepositoryBackend::Memory);
+    }
+
+    #[test]
+    fn test_registry_settings_precedence() {
+        let mut config = ServerConfig::parse(
+            r#"
+            [registry]
+            api_url = "https://api.raktar.io"
+            index_url = "sparse+https://index.raktar.io/"
+            auth_required = false
+            "#,
+        )
+        .unwrap();
+        let flags = RegistrySettings {
+            index_url: Some("sparse+https://cli.raktar.io/".to_string()),
+            ..RegistrySettings::default()
+        };
+        config.registry = flags.over(config.registry);
+
+        let registry_config = config
+            .registry_config_from(|key| match key {
+                "DOMAIN_NAME" => Some("env.raktar.io".to_string()),
+                "API_URL" => Some("https://env.raktar.io".to_string()),
+                "INDEX_URL" => Some("sparse+https://env.raktar.io/".to_string()),
+                "AUTH_REQUIRED" => Some("true".to_string()),
+                _ => None,
+            })
+            .unwrap();
+
+        // flags win over the config file, which wins over the environment
+        assert_eq!(registry_config.index_url, "sparse+https://cli.raktar.io/");
+        assert_eq!(registry_config.api_url, "https://api.raktar.io");
+        assert!(!registry_config.auth_required);
+        assert_eq!(
+            registry_config.download_url_template,
+            "https://env.raktar.io/api/v1/crates"
+        );
+    }
+
+    #[test]
+    fn test_registry_settings_hide_secrets() {
+        let config = ServerConfig::parse(
+            r#"
+            [registry]
+            domain_name = "raktar.io"
+            session_secret = "a secret that is long enough to use"
+            "#,
+        )
+        .unwrap();
+
+        let debug = format!("{:?}", config);
+
+        assert!(debug.contains("raktar.io"));
+        assert!(!debug.contains("a secret"));
+    }
+
+    #[test]
+    fn test_unknown_backend() {
+        let err = ServerConfig::parse("[storage]\ntype = \"gcs\"").unwrap_err();
+
+        assert!(err.to_string().starts_with("invalid config file"));
+    }
+}
@@ -1,14 +1,132 @@
+use axum::body::Bytes;
+use futures::stream::{self, BoxStream, TryStreamExt};
+use std::fmt;
+use std::io;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use semver::Version;
 
 use crate::error::AppResult;
 
+/// The contents of a stored crate, read in chunks.
+pub type CrateByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// A stored crate, read in chunks so that it can be checked without loading it into memory.
+pub struct CrateBody {
+    /// The size of the crate in bytes.
+    pub content_length: u64,
+    pub stream: CrateByteStream,
+}
+
+impl fmt::Debug for CrateBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CrateBody")
+            .field("content_length", &self.content_length)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CrateBody {
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            content_length: data.len() as u64,
+            stream: Box::pin(stream::once(async { Ok(Bytes::from(data)) })),
+        }
+    }
+
+    /// Reads the whole crate into memory.
+    pub async fn into_bytes(self) -> io::Result<Vec<u8>> {
+        self.stream
+            .try_fold(Vec::new(), |mut data, chunk| async move {
+                data.extend_from_slice(&chunk);
+                Ok(data)
+            })
+            .await
+    }
+}
+
+/// A crate version found in storage.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StoredCrate {
+    pub name: String,
+    pub version: Version,
+    /// When the crate was stored.
+    pub last_modified: SystemTime,
+}
+
+impl StoredCrate {
+    /// Parses the `{name}-{version}.crate` file name of a crate stored under `name`.
+    pub(crate) fn from_file_name(
+        name: &str,
+        file_name: &str,
+        last_modified: SystemTime,
+    ) -> Option<Self> {
+        let version = file_name
+            .strip_prefix(name)?
+            .strip_prefix('-')?
+            .strip_suffix(".crate")?;
+
+        Some(Self {
+            name: name.to_string(),
+            version: Version::parse(version).ok()?,
+            last_modified,
+        })
+    }
+}
+
 #[async_trait::async_trait]
 pub trait CrateStorage {
+    /// Stores a new version of a crate, failing with
+    /// [`AppError::DuplicateCrateVersion`](crate::error::AppError::DuplicateCrateVersion)
+    /// instead of overwriting a version that's already stored.
     async fn store_crate(&self, crate_name: &str, version: Version, data: Vec<u8>)
         -> AppResult<()>;
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>>;
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateBody>;
+    /// Gets a short-lived URL the client can be redirected to, to download the crate
+    /// directly from the backend. Backends that can't do this return `None`, and the
+    /// crate is served with [`CrateStorage::get_crate`] instead.
+    async fn get_download_url(
+        &self,
+        _crate_name: &str,
+        _version: &Version,
+    ) -> AppResult<Option<String>> {
+        Ok(None)
+    }
+    /// Removes a stored crate, succeeding if the crate was not stored in the first place.
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()>;
+    /// Lists every stored crate, in no particular order.
+    async fn list_crates(&self) -> AppResult<Vec<StoredCrate>>;
 }
 
 pub type DynCrateStorage = Arc<dyn CrateStorage + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stored_crate_from_file_name() {
+        let now = SystemTime::now();
+        assert_eq!(
+            StoredCrate::from_file_name("foo-bar", "foo-bar-1.0.0-alpha.1.crate", now),
+            Some(StoredCrate {
+                name: "foo-bar".to_string(),
+                version: Version::parse("1.0.0-alpha.1").unwrap(),
+                last_modified: now,
+            })
+        );
+        assert_eq!(
+            StoredCrate::from_file_name("foo", "bar-1.0.0.crate", now),
+            None
+        );
+        assert_eq!(
+            StoredCrate::from_file_name("foo", "foo-1.0.0.crate.sha256", now),
+            None
+        );
+        assert_eq!(
+            StoredCrate::from_file_name("foo", "foo-latest.crate", now),
+            None
+        );
+    }
+}
@@ -0,0 +1,407 @@
+use anyhow::anyhow;
+use axum::body::Bytes;
+use futures::stream;
+use hex::ToHex;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::{CrateBody, CrateByteStream, CrateStorage, StoredCrate};
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Stores crates in a directory, with the same `crates/{name}/{name}-{version}.crate`
+/// layout as [`S3Storage`](crate::storage::S3Storage).
+///
+/// Next to every crate a `.sha256` file holds the checksum of the crate,
+/// which is verified whenever the crate is read back.
+#[derive(Clone)]
+pub struct FilesystemStorage {
+    root: PathBuf,
+}
+
+impl FilesystemStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn new_from_env() -> Self {
+        Self::new(
+            std::env::var("CRATES_DIRECTORY").expect("CRATES_DIRECTORY to be set in environment"),
+        )
+    }
+
+    pub fn crate_path(&self, name: &str, version: &Version) -> PathBuf {
+        self.root
+            .join("crates")
+            .join(name)
+            .join(format!("{}-{}.crate", name, version))
+    }
+
+    fn checksum_path(crate_path: &Path) -> PathBuf {
+        crate_path.with_extension("crate.sha256")
+    }
+
+    /// Checks that the crate name can't point outside of its directory.
+    fn is_valid_name(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+#[async_trait::async_trait]
+impl CrateStorage for FilesystemStorage {
+    async fn store_crate(
+        &self,
+        crate_name: &str,
+        version: Version,
+        data: Vec<u8>,
+    ) -> AppResult<()> {
+        if !Self::is_valid_name(crate_name) {
+            return Err(AppError::InvalidCrateName(format!(
+                "invalid crate name {}",
+                crate_name
+            )));
+        }
+
+        let path = self.crate_path(crate_name, &version);
+        let checksum: String = Sha256::digest(&data).encode_hex();
+        // the crate claims the version, so that a competing upload can't replace it or
+        // its checksum, the crate isn't in the index until after its checksum is written
+        match write_new(&path, &data).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                return Err(AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version,
+                });
+            }
+            Err(err) => {
+                let error_message = err.to_string();
+                error!(error_message, "failed to write crate file");
+                return Err(anyhow!("unexpected error in storing crate").into());
+            }
+        }
+        write_atomically(&Self::checksum_path(&path), checksum.as_bytes()).await
+    }
+
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateBody> {
+        let not_found = || AppError::NonExistentCrateVersion {
+            crate_name: crate_name.to_string(),
+            version: version.clone(),
+        };
+        if !Self::is_valid_name(crate_name) {
+            return Err(not_found());
+        }
+
+        let path = self.crate_path(crate_name, &version);
+        let file = match fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Err(not_found()),
+            Err(_) => return Err(anyhow!("unexpected error in reading crate").into()),
+        };
+        let content_length = file
+            .metadata()
+            .await
+            .map_err(|_| anyhow!("unexpected error in reading crate"))?
+            .len();
+        let expected_checksum = fs::read_to_string(Self::checksum_path(&path))
+            .await
+            .map_err(|_| anyhow!("unexpected error in reading crate checksum"))?;
+
+        let read = VerifiedRead {
+            file,
+            hasher: Sha256::new(),
+            expected_checksum: expected_checksum.trim().to_string(),
+            path,
+        };
+        Ok(CrateBody {
+            content_length,
+            stream: read.into_stream(),
+        })
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        if !Self::is_valid_name(crate_name) {
+            return Ok(());
+        }
+
+        let path = self.crate_path(crate_name, &version);
+        for path in [Self::checksum_path(&path), path] {
+            match fs::remove_file(&path).await {
+                Ok(_) => {}
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(_) => return Err(anyhow!("unexpected error in deleting crate").into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn list_crates(&self) -> AppResult<Vec<StoredCrate>> {
+        let unexpected_error = |_| anyhow!("unexpected error in listing crates");
+        let mut crates = Vec::new();
+        let mut directories = match fs::read_dir(self.root.join("crates")).await {
+            Ok(directories) => directories,
+            // nothing has been stored yet
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(crates),
+            Err(err) => return Err(unexpected_error(err).into()),
+        };
+
+        while let Some(directory) = directories.next_entry().await.map_err(unexpected_error)? {
+            let name = directory.file_name().to_string_lossy().to_string();
+            let file_type = directory.file_type().await.map_err(unexpected_error)?;
+            if !Self::is_valid_name(&name) || !file_type.is_dir() {
+                continue;
+            }
+
+            let mut files = fs::read_dir(directory.path())
+                .await
+                .map_err(unexpected_error)?;
+            while let Some(file) = files.next_entry().await.map_err(unexpected_error)? {
+                let file_name = file.file_name().to_string_lossy().to_string();
+                let last_modified = match file.metadata().await.and_then(|m| m.modified()) {
+                    Ok(last_modified) => last_modified,
+                    // temporary files are removed while they're being listed
+                    Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                    Err(err) => return Err(unexpected_error(err).into()),
+                };
+                crates.extend(StoredCrate::from_file_name(
+                    &name,
+                    &file_name,
+                    last_modified,
+                ));
+            }
+        }
+
+        Ok(crates)
+    }
+}
+
+/// Reads a crate in chunks, and fails the last read if the crate doesn't match its checksum.
+struct VerifiedRead {
+    file: fs::File,
+    hasher: Sha256,
+    expected_checksum: String,
+    path: PathBuf,
+}
+
+impl VerifiedRead {
+    fn into_stream(self) -> CrateByteStream {
+        Box::pin(stream::try_unfold(self, |mut read| async move {
+            let mut buffer = vec![0; READ_CHUNK_SIZE];
+            let length = read.file.read(&mut buffer).await?;
+            if length == 0 {
+                let checksum: String = read.hasher.finalize().encode_hex();
+                if checksum != read.expected_checksum {
+                    error!(
+                        path = read.path.display().to_string(),
+                        "stored crate does not match its checksum"
+                    );
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "stored crate is corrupted",
+                    ));
+                }
+                return Ok(None);
+            }
+
+            buffer.truncate(length);
+            read.hasher.update(&buffer);
+            Ok(Some((Bytes::from(buffer), read)))
+        }))
+    }
+}
+
+/// Writes the file to a temporary file in the same directory first, and then moves it
+/// into place, so that readers never see a partially written file.
+async fn write_atomically(path: &Path, data: &[u8]) -> AppResult<()> {
+    let result = async {
+        let temp_path = write_temp(path, data).await?;
+        let result = fs::rename(&temp_path, path).await;
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path).await;
+        }
+        result
+    }
+    .await;
+
+    if let Err(err) = result {
+        let error_message = err.to_string();
+        error!(error_message, "failed to write crate file");
+        return Err(anyhow!("unexpected error in storing crate").into());
+    }
+
+    Ok(())
+}
+
+/// Writes the file like [`write_atomically`], but fails with `AlreadyExists`
+/// instead of replacing a file that's already there.
+async fn write_new(path: &Path, data: &[u8]) -> io::Result<()> {
+    let temp_path = write_temp(path, data).await?;
+    // unlike renaming, linking never replaces the target
+    let result = fs::hard_link(&temp_path, path).await;
+    let _ = fs::remove_file(&temp_path).await;
+
+    result
+}
+
+/// Writes the data to a new temporary file next to the path.
+async fn write_temp(path: &Path, data: &[u8]) -> io::Result<PathBuf> {
+    let invalid_path = || io::Error::new(ErrorKind::InvalidInput, "invalid crate path");
+    let directory = path.parent().ok_or_else(invalid_path)?;
+    let file_name = path.file_name().ok_or_else(invalid_path)?.to_string_lossy();
+    let temp_path = directory.join(format!(".{}.{}.tmp", file_name, Uuid::new_v4()));
+
+    let result = async {
+        fs::create_dir_all(directory).await?;
+        let mut file = fs::File::create(&temp_path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => Ok(temp_path),
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path).await;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_storage() -> FilesystemStorage {
+        let root = std::env::temp_dir().join(format!("raktar-{}", Uuid::new_v4()));
+        FilesystemStorage::new(root)
+    }
+
+    #[tokio::test]
+    async fn test_store_and_get_crate() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+
+        storage
+            .store_crate("foo", version.clone(), b"crate".to_vec())
+            .await
+            .unwrap();
+
+        let path = storage.crate_path("foo", &version);
+        assert!(path.ends_with("crates/foo/foo-0.1.0.crate"));
+        assert!(path.exists());
+        let body = storage.get_crate("foo", version).await.unwrap();
+        assert_eq!(body.content_length, 5);
+        assert_eq!(body.into_bytes().await.unwrap(), b"crate");
+    }
+
+    #[tokio::test]
+    async fn test_missing_crate() {
+        let storage = build_storage();
+
+        let err = storage
+            .get_crate("foo", Version::new(0, 1, 0))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, AppError::NonExistentCrateVersion { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_crate_is_not_returned() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        storage
+            .store_crate("foo", version.clone(), b"crate".to_vec())
+            .await
+            .unwrap();
+
+        // large enough to be read in several chunks
+        let tampered = vec![b'x'; 200 * 1024];
+        std::fs::write(storage.crate_path("foo", &version), &tampered).unwrap();
+
+        let body = storage.get_crate("foo", version).await.unwrap();
+        let err = body.into_bytes().await.unwrap_err();
+        assert_eq!(err.to_string(), "stored crate is corrupted");
+    }
+
+    #[tokio::test]
+    async fn test_stored_crate_is_not_overwritten() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        storage
+            .store_crate("foo", version.clone(), b"crate".to_vec())
+            .await
+            .unwrap();
+
+        let err = storage
+            .store_crate("foo", version.clone(), b"other".to_vec())
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, AppError::DuplicateCrateVersion { .. }));
+        let body = storage.get_crate("foo", version).await.unwrap();
+        assert_eq!(body.into_bytes().await.unwrap(), b"crate");
+    }
+
+    #[tokio::test]
+    async fn test_delete_crate() {
+        let storage = build_storage();
+        let version = Version::new(0, 1, 0);
+        storage
+            .store_crate("foo", version.clone(), b"crate".to_vec())
+            .await
+            .unwrap();
+
+        storage.delete_crate("foo", version.clone()).await.unwrap();
+        // deleting it again is not an error
+        storage.delete_crate("foo", version.clone()).await.unwrap();
+
+        let err = storage.get_crate("foo", version).await.unwrap_err();
+        assert!(matches!(err, AppError::NonExistentCrateVersion { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_list_crates() {
+        let storage = build_storage();
+        assert_eq!(storage.list_crates().await.unwrap(), vec![]);
+
+        for version in ["0.1.0", "0.2.0"] {
+            let version = Version::parse(version).unwrap();
+            storage
+                .store_crate("foo", version, b"crate".to_vec())
+                .await
+                .unwrap();
+        }
+
+        let mut crates = storage.list_crates().await.unwrap();
+        crates.sort_by(|a, b| a.version.cmp(&b.version));
+        let versions: Vec<_> = crates
+            .into_iter()
+            .map(|stored| format!("{}-{}", stored.name, stored.version))
+            .collect();
+        assert_eq!(versions, vec!["foo-0.1.0", "foo-0.2.0"]);
+    }
+
+    #[tokio::test]
+    async fn test_names_cannot_escape_the_root() {
+        let storage = build_storage();
+
+        let err = storage
+            .get_crate("..", Version::new(0, 1, 0))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, AppError::NonExistentCrateVersion { .. }));
+    }
+}
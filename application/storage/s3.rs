@@ -1,27 +1,58 @@
 use anyhow::anyhow;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::Client;
+use aws_smithy_http::result::SdkError;
+use futures::TryStreamExt;
+use http::HeaderValue;
 use semver::Version;
+use std::io;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 
 use crate::error::{AppError, AppResult};
-use crate::storage::CrateStorage;
+use crate::storage::{CrateBody, CrateStorage, StoredCrate};
 
 #[derive(Clone)]
 pub struct S3Storage {
     bucket: String,
     prefix: String,
     client: Client,
+    /// When set, downloads are redirected to presigned URLs that are valid this long.
+    presigned_url_expiry: Option<Duration>,
 }
 
 impl S3Storage {
+    /// Uses the bucket in `CRATES_BUCKET_NAME`.
+    ///
+    /// Setting `PRESIGNED_URL_EXPIRY_SECONDS` turns on redirecting downloads
+    /// to presigned URLs, instead of serving the crates through the registry.
     pub async fn new() -> Self {
         let aws_config = aws_config::from_env().load().await;
         let bucket =
             std::env::var("CRATES_BUCKET_NAME").expect("S3 bucket to be configured in env");
+        let presigned_url_expiry =
+            std::env::var("PRESIGNED_URL_EXPIRY_SECONDS")
+                .ok()
+                .map(|seconds| {
+                    let seconds = u64::from_str(&seconds)
+                        .expect("PRESIGNED_URL_EXPIRY_SECONDS to be a number of seconds");
+                    Duration::from_secs(seconds)
+                });
+
+        Self::with_client(Client::new(&aws_config), bucket, presigned_url_expiry)
+    }
+
+    pub fn with_client(
+        client: Client,
+        bucket: String,
+        presigned_url_expiry: Option<Duration>,
+    ) -> Self {
         Self {
             bucket,
             prefix: "crates".to_string(),
-            client: Client::new(&aws_config),
+            client,
+            presigned_url_expiry,
         }
     }
 
@@ -39,21 +70,38 @@ impl CrateStorage for S3Storage {
         data: Vec<u8>,
     ) -> AppResult<()> {
         let key = self.crate_key(crate_name, &version);
-        match self
+        let operation = self
             .client
             .put_object()
             .bucket(&self.bucket)
             .key(key)
             .body(data.into())
-            .send()
+            .customize()
             .await
-        {
+            .map_err(|_| anyhow!("unexpected error in storing crate"))?
+            // a version that's already stored is never overwritten
+            .mutate_request(|request| {
+                request
+                    .headers_mut()
+                    .insert("If-None-Match", HeaderValue::from_static("*"));
+            });
+
+        match operation.send().await {
             Ok(_) => Ok(()),
+            // S3 answers 409 instead of 412 while a competing upload is in progress
+            Err(SdkError::ServiceError(err))
+                if matches!(err.raw().http().status().as_u16(), 409 | 412) =>
+            {
+                Err(AppError::DuplicateCrateVersion {
+                    crate_name: crate_name.to_string(),
+                    version,
+                })
+            }
             Err(_) => Err(anyhow::anyhow!("unexpected error in storing crate").into()),
         }
     }
 
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateBody> {
         let key = self.crate_key(crate_name, &version);
         match self
             .client
@@ -74,12 +122,129 @@ impl CrateStorage for S3Storage {
 
                 Err(mapped_err)
             }
-            Ok(output) => output
-                .body
-                .collect()
-                .await
-                .map_err(|_| anyhow!("failed to collect bytes").into())
-                .map(|data| data.into_bytes().to_vec()),
+            Ok(output) => {
+                let content_length = u64::try_from(output.content_length())
+                    .map_err(|_| anyhow!("crate in S3 has no content length"))?;
+                let stream = output.body.map_err(io::Error::other);
+                Ok(CrateBody {
+                    content_length,
+                    stream: Box::pin(stream),
+                })
+            }
         }
     }
+
+    async fn get_download_url(
+        &self,
+        crate_name: &str,
+        version: &Version,
+    ) -> AppResult<Option<String>> {
+        let Some(expiry) = self.presigned_url_expiry else {
+            return Ok(None);
+        };
+
+        let presigning_config = PresigningConfig::expires_in(expiry)
+            .map_err(|_| anyhow!("invalid expiry for presigned URLs"))?;
+        let request = self
+            .client
+            .get_object()
+            .bucket(&self.bucket)
+            .key(self.crate_key(crate_name, version))
+            .presigned(presigning_config)
+            .await
+            .map_err(|_| anyhow!("unexpected error in presigning crate download"))?;
+
+        Ok(Some(request.uri().to_string()))
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        let key = self.crate_key(crate_name, &version);
+        match self
+            .client
+            .delete_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .send()
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(_) => Err(anyhow::anyhow!("unexpected error in deleting crate").into()),
+        }
+    }
+
+    async fn list_crates(&self) -> AppResult<Vec<StoredCrate>> {
+        let pages: Vec<_> = self
+            .client
+            .list_objects_v2()
+            .bucket(&self.bucket)
+            .prefix(format!("{}/", self.prefix))
+            .into_paginator()
+            .send()
+            .try_collect()
+            .await?;
+
+        let crates = pages
+            .iter()
+            .flat_map(|page| page.contents().unwrap_or_default())
+            .filter_map(|object| {
+                let (name, file_name) = object
+                    .key()?
+                    .strip_prefix(&self.prefix)?
+                    .strip_prefix('/')?
+                    .split_once('/')?;
+                // S3 always lists the time, but when it doesn't, the crate is taken to be new
+                let last_modified = object
+                    .last_modified()
+                    .and_then(|time| SystemTime::try_from(*time).ok())
+                    .unwrap_or_else(SystemTime::now);
+                StoredCrate::from_file_name(name, file_name, last_modified)
+            })
+            .collect();
+
+        Ok(crates)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::config::{Credentials, Region};
+
+    fn build_storage(presigned_url_expiry: Option<Duration>) -> S3Storage {
+        let config = aws_sdk_s3::Config::builder()
+            .region(Region::new("eu-west-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .build();
+        let client = Client::from_conf(config);
+
+        S3Storage::with_client(client, "crates-bucket".to_string(), presigned_url_expiry)
+    }
+
+    #[tokio::test]
+    async fn test_presigned_download_url() {
+        let storage = build_storage(Some(Duration::from_secs(60)));
+
+        let url = storage
+            .get_download_url("foo", &Version::new(0, 1, 0))
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert!(url.starts_with(
+            "https://crates-bucket.s3.eu-west-1.amazonaws.com/crates/foo/foo-0.1.0.crate?"
+        ));
+        assert!(url.contains("X-Amz-Expires=60"));
+    }
+
+    #[tokio::test]
+    async fn test_no_download_url_without_expiry() {
+        let storage = build_storage(None);
+
+        let url = storage
+            .get_download_url("foo", &Version::new(0, 1, 0))
+            .await
+            .unwrap();
+
+        assert_eq!(url, None);
+    }
 }
@@ -30,6 +30,8 @@ class Database(Construct):
             billing_mode=dynamodb.BillingMode.PROVISIONED,
             read_capacity=5,
             write_capacity=1,
+            # used signed tokens are only kept until they are too old to be accepted
+            time_to_live_attribute="ttl",
         )
 
     @staticmethod
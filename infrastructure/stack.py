@@ -61,6 +61,11 @@ class RaktarStack(Stack):
             hosted_zone=hosted_zone,
             hosted_ui_certificate=hosted_ui_certificate,
         )
+        backend_function.add_environment(
+            "JWT_ISSUER",
+            f"https://cognito-idp.{self.region}.amazonaws.com/{user_pool.user_pool_id}",
+        )
+        backend_function.add_environment("JWT_AUDIENCE", user_pool.user_pool_client_id)
         table.grant_read_write_data(backend_function)
         table.grant_read_write_data(pre_token_function)
         bucket.grant_read_write(backend_function)
@@ -0,0 +1,48 @@
+-- The schema is kept to the subset of SQL that SQLite and PostgreSQL both support.
+
+CREATE TABLE users (
+    id BIGINT PRIMARY KEY,
+    login TEXT NOT NULL UNIQUE,
+    given_name TEXT NOT NULL,
+    family_name TEXT NOT NULL
+);
+
+CREATE TABLE crates (
+    name TEXT PRIMARY KEY,
+    -- the name after `-`/`_` and case normalization, see `canonical_crate_name`
+    canonical_name TEXT NOT NULL UNIQUE,
+    max_version TEXT NOT NULL,
+    description TEXT NOT NULL,
+    index_revision BIGINT NOT NULL,
+    index_last_modified TEXT NOT NULL
+);
+
+CREATE TABLE crate_owners (
+    crate_name TEXT NOT NULL REFERENCES crates (name),
+    user_id BIGINT NOT NULL,
+    PRIMARY KEY (crate_name, user_id)
+);
+
+CREATE TABLE crate_versions (
+    crate_name TEXT NOT NULL REFERENCES crates (name),
+    version TEXT NOT NULL,
+    checksum TEXT NOT NULL,
+    yanked BOOLEAN NOT NULL,
+    links TEXT,
+    -- the dependencies and features of the index entry, as JSON
+    dependencies TEXT NOT NULL,
+    features TEXT NOT NULL,
+    -- the metadata sent by `cargo publish`, as JSON
+    metadata TEXT NOT NULL,
+    PRIMARY KEY (crate_name, version)
+);
+
+CREATE TABLE tokens (
+    -- hex encoded SHA-256 hash of the token
+    token_hash TEXT PRIMARY KEY,
+    token_id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    user_id BIGINT NOT NULL
+);
+
+CREATE INDEX tokens_user_id ON tokens (user_id);
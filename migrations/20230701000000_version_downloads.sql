@@ -0,0 +1,10 @@
+-- Daily download counts, there is no reference to `crate_versions`
+-- so that counting a download never fails because of the crate it's for.
+CREATE TABLE version_downloads (
+    crate_name TEXT NOT NULL,
+    version TEXT NOT NULL,
+    -- the day in UTC, formatted as YYYY-MM-DD
+    date TEXT NOT NULL,
+    downloads BIGINT NOT NULL,
+    PRIMARY KEY (crate_name, version, date)
+);
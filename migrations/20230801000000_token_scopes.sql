@@ -0,0 +1,4 @@
+-- The scopes a token is limited to, as JSON arrays.
+-- NULL means the token is not limited, which is how tokens created before scopes behave.
+ALTER TABLE tokens ADD COLUMN endpoint_scopes TEXT;
+ALTER TABLE tokens ADD COLUMN crate_scopes TEXT;
@@ -0,0 +1,4 @@
+-- Timestamps in RFC 3339 format. Tokens created before this migration have no creation time.
+ALTER TABLE tokens ADD COLUMN created_at TEXT;
+ALTER TABLE tokens ADD COLUMN expires_at TEXT;
+ALTER TABLE tokens ADD COLUMN last_used_at TEXT;
@@ -0,0 +1,8 @@
+-- The PASERK public key of tokens Cargo signs every request with, stored under the id of the key.
+ALTER TABLE tokens ADD COLUMN public_key TEXT;
+
+-- Signed tokens that were used, kept until they are too old to be accepted anyway.
+CREATE TABLE signed_tokens (
+    token_hash TEXT PRIMARY KEY,
+    expires_at TEXT NOT NULL
+);
@@ -0,0 +1,88 @@
+mod common;
+
+use axum::body::{Body, Bytes};
+use axum::http::{Request, StatusCode};
+use raktar::auth::AuthenticatedUser;
+use raktar::cargo_api::publish::publish_crate;
+use raktar::config::{AnonymousRead, RegistryConfig};
+use raktar::repository::DynRepository;
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::http::{build_get_request, send_request};
+use common::memory_storage::MemoryStorage;
+use common::setup::build_repository;
+
+#[tokio::test]
+#[traced_test]
+async fn test_index_requires_token_by_default() {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let config = RegistryConfig::for_domain("raktar.io");
+    let app = build_router(repository, storage, config);
+
+    let (status, _) = send_request(&app, build_get_request("/te/st/testcrate_1", None)).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_anonymous_read_for_matching_crates() {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let user = AuthenticatedUser::new(1);
+    publish_crate(
+        user,
+        storage.clone(),
+        repository.clone(),
+        Bytes::from_static(CRATE_BYTES),
+    )
+    .await
+    .expect("publish to succeed");
+
+    let config = RegistryConfig {
+        anonymous_read: AnonymousRead::Crates(vec!["testcrate-*".parse().unwrap()]),
+        ..RegistryConfig::for_domain("raktar.io")
+    };
+    let app = build_router(repository, storage, config);
+
+    let (status, body) = send_request(&app, build_get_request("/te/st/testcrate_1", None)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body.contains("\"vers\":\"0.1.1\""));
+
+    let uri = "/api/v1/crates/testcrate_1/0.1.1/download";
+    let (status, _) = send_request(&app, build_get_request(uri, None)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = send_request(&app, build_get_request("/ot/he/other_crate", None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_anonymous_read_does_not_allow_writes() {
+    let repository = Arc::new(build_repository().await) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let config = RegistryConfig {
+        anonymous_read: AnonymousRead::AllCrates,
+        ..RegistryConfig::for_domain("raktar.io")
+    };
+    let app = build_router(repository, storage, config);
+
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/api/v1/crates/new")
+        .body(Body::from(CRATE_BYTES.to_vec()))
+        .unwrap();
+    let (status, _) = send_request(&app, request).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let uri = "/api/v1/crates/testcrate_1/owners";
+    let (status, _) = send_request(&app, build_get_request(uri, None)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+static CRATE_BYTES: &[u8; 1374] = b":\x02\0\0{\"name\":\"testcrate_1\",\"vers\":\"0.1.1\",\"deps\":[{\"optional\":false,\"default_features\":true,\"name\":\"serde\",\"features\":[\"derive\"],\"version_req\":\"^1.0.150\",\"target\":null,\"kind\":\"normal\",\"registry\":\"https://github.com/rust-lang/crates.io-index\"}],\"features\":{},\"authors\":[],\"description\":\"A private crate for testing purposes.\",\"documentation\":null,\"homepage\":null,\"readme\":\"# Test Crate 1\\n\\nA crate for testing Raktar.\\n\",\"readme_file\":\"README.md\",\"keywords\":[],\"categories\":[],\"license\":null,\"license_file\":null,\"repository\":null,\"badges\":{},\"links\":null,\"rust_version\":null}\x1c\x03\0\0\x1f\x8b\x08\x08\0\0\0\0\x02\xfftestcrate_1-0.1.1.crate\0\xedX\xdfo\xda0\x10\xe6\xd9\x7f\xc5)}i%\x9a&\xfc\x94:\xf5!\x03\xb6!\xb5\xabD\x99\xa6\xaab\xabILb\xe1\xc4\xc8v\xcaX\xd5\xff}\x97@K\xa1h}\x18EC\xcd\xf7\x12\xc7\xb1\xef\xce\x97\xfb>;1L\x1b_Q\xc3~\xba\xc7\x8e\xed\xda\xeeI\x8b\xaaP\xdaF\xc6\xa2\xb4%8\x88F\xad\xb6\xb1\x1f\xe1Vj\xeec;\xbb\xc5\xb6\x8b}\xf5\x92S\xda\x01Rm\xa8\x02(\xbdS\x1c@\xffK\xf7\n>u\xcf;\x80W\xef[\xff\xf2\xc2\xebw[\xde\xf9\xf95|\xee|\xed\xf4\xbc~\xa7\r\x1f\xaf\xa1\xe5\xf5>_\x92\x03r\0\xdf#\x96@:\x11\x92\x06<\t!/\x1f\rF\x82\x89\x18(\x16rm\xd4\x0c\xf2:\x82)\x17\x02h\x8a\xe5D\r\xf7\xa9\x1034`%R\xc5T\xf0\xdf\xcc\x82e\xb9\xc1\x88\x0b\xb43\x92\nb\xfa\x8b\xe3\0\xf0e<\xc1yC.\xb8\xc9&N\xb9\x89\0\x8d\xc0\x1dS\x9a\xcbD\x83\x1c-\x1c\xd1$\xc0'Zb\0S\xc5\r\x83[\x9c\x19\xddB\xc0&,\tX\xe2s\xa6\xd1\x82\x91\xcb\x08\x0f\x99\x1d\xda\xe5E\xfc6\x97G+\x83\xed|\xad\xdd\x11\xccd\nTe+\x9b\xaf\xd7D\\\xe7\xb1\xc2\x90\x01\x9df\x8fLDM\xbez\xa9x\xc8\x13\x8c|\xb9\xac<l\x0cY\xf01\x133\x10R\x8e\xb3\xf0g\x10\xf0\xd1\x88)\x96\x188\xcc\x82\x8fS?\x82X\xce\x1di\x99\xd0\xa1`G\x18\x04\\1\xf6\xcc\x9c\x9d\xb9\xc8\x93\xb4\xe2\xcf\x97\x89AS\x185\xb9\x99P\x7fLC6 ,\xe0\x06\xb3\x04g`U\x9c\x8ak\x91\x84\xc6,\xbb3K\xd6[d\x91\xcb\xac?W\0\x8b\x04L\xfb\x8aO\x1e\xe7z0Q\xfc\x0eG\xcfS5w\x8e\x16\xb2dLR5\x91\x1a\xb3e\x91,?s\xf3\xbd\x8e\xd7\xbe\xe8\xd8q`a4+9\xd5L\x05\x18\xd83\x97?\\\x1b\xbd\xd6\x1d\x8b\x8c\x185\xa9\xc2\n8\x83\x1b+`\xe8\x92Y\x03R*\xf0\x960\x7f\xd1\xff\xbc\xd4\xb6\xa8\xff\x0b\x81_\xbf:\xd5J\xa3\xe4:\xb5\x86\xeb`\x95V\xb3~\xb7\xd6\xac\xba\xfb\xa5\xff\xeb\x8b\xdb\x13,\xc5\xe2M\xb5\xe1\x85\x12m\x16\x8bU\xb5\x18\x90\\.p\xd0=lR\x8c2l\x94\x0cx(D\xe3\x1f\xf8\xff\xf4>\xb6\xe6\xe35\xfe;\xf5\xda:\xff\xabU\xb7Y\xf0\x7f7\xe7?\xac\0h\xe5\xe4u\t\xf16\xf0\xb8G\xc7\x98#\xbb`\xd5\xbb\xe0\xbfBF\x1c\x1b)\x85\x1fQ\x9el\xe3C\xf0U\xfeW\x9b\xeb\xfc\xaf7\xddb\xff\xdf\xc9\xfe\xff\xf4\xa6\x07\x04/I\xc2D\xb6\xc7&<\x8c\x8c\x98Y\x05A\xde\x1d\xff\xb5\xf2O\x04\x1f\xdaJ\xefj\xffw\x1b/\xf7\xff\xe6\xbe\xfd\xff\xd9S\xfe\xa7\x9aA~\xca>=\xbdo3l\xf1\xfc\xbfL\x19\xae\x1e\x9b\x0f\x1f\x089\xb8\x99\x1f\xae\x0f\xdbl\x98\x86e\xd8<\xf2\x08\x0f\xecF\xa5\xbe\xc9\xcf\x14mj(\xdc\x13@\x04\xd8<\x85Tg\xc3Iq:/P\xa0@\x81\xff\x01\x7f\0\xc4\xbd\n+\0\x1a\0\0";
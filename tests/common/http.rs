@@ -0,0 +1,23 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use tower::ServiceExt;
+
+#[allow(dead_code)] // not all tests use this
+pub async fn send_request(app: &Router, request: Request<Body>) -> (StatusCode, String) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let bytes = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+    (status, String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[allow(dead_code)] // not all tests use this
+pub fn build_get_request(uri: &str, token: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().method("GET").uri(uri);
+    if let Some(token) = token {
+        builder = builder.header("Authorization", token);
+    }
+
+    builder.body(Body::empty()).unwrap()
+}
@@ -1,14 +1,27 @@
 use async_trait::async_trait;
 use semver::Version;
 use std::collections::HashMap;
+use std::time::SystemTime;
 use tokio::sync::RwLock;
 
-use raktar::error::AppResult;
-use raktar::storage::CrateStorage;
+use raktar::error::{AppError, AppResult};
+use raktar::storage::{CrateBody, CrateStorage, StoredCrate};
 
+/// The crate and when it was stored.
+type StoredData = (Vec<u8>, SystemTime);
+
+#[allow(dead_code)] // not all tests use this
 #[derive(Debug, Default)]
 pub struct MemoryStorage {
-    data: RwLock<HashMap<(String, Version), Vec<u8>>>,
+    data: RwLock<HashMap<(String, Version), StoredData>>,
+}
+
+#[allow(dead_code)] // not all tests use this
+impl MemoryStorage {
+    pub async fn contains(&self, crate_name: &str, version: &Version) -> bool {
+        let key = (crate_name.to_string(), version.clone());
+        self.data.read().await.contains_key(&key)
+    }
 }
 
 #[async_trait]
@@ -19,18 +32,52 @@ impl CrateStorage for MemoryStorage {
         version: Version,
         data: Vec<u8>,
     ) -> AppResult<()> {
-        let key = (crate_name.to_string(), version);
+        let key = (crate_name.to_string(), version.clone());
         let mut lock = self.data.write().await;
-        lock.insert(key, data);
+        if lock.contains_key(&key) {
+            return Err(AppError::DuplicateCrateVersion {
+                crate_name: crate_name.to_string(),
+                version,
+            });
+        }
+        lock.insert(key, (data, SystemTime::now()));
 
         Ok(())
     }
 
-    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<Vec<u8>> {
+    async fn get_crate(&self, crate_name: &str, version: Version) -> AppResult<CrateBody> {
         let key = (crate_name.to_string(), version);
         let lock = self.data.read().await;
-        let data = lock.get(&key).cloned().unwrap();
+        let (data, _) =
+            lock.get(&key)
+                .cloned()
+                .ok_or_else(|| AppError::NonExistentCrateVersion {
+                    crate_name: key.0.clone(),
+                    version: key.1.clone(),
+                })?;
+
+        Ok(CrateBody::from_bytes(data))
+    }
+
+    async fn delete_crate(&self, crate_name: &str, version: Version) -> AppResult<()> {
+        let key = (crate_name.to_string(), version);
+        let mut lock = self.data.write().await;
+        lock.remove(&key);
+
+        Ok(())
+    }
+
+    async fn list_crates(&self) -> AppResult<Vec<StoredCrate>> {
+        let lock = self.data.read().await;
+        let crates = lock
+            .iter()
+            .map(|((name, version), (_, last_modified))| StoredCrate {
+                name: name.clone(),
+                version: version.clone(),
+                last_modified: *last_modified,
+            })
+            .collect();
 
-        Ok(data)
+        Ok(crates)
     }
 }
@@ -1,3 +1,5 @@
 pub mod graphql;
+pub mod http;
 pub mod memory_storage;
+#[allow(dead_code)] // not all tests use this
 pub mod setup;
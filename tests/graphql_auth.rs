@@ -0,0 +1,119 @@
+// local builds let GraphQL requests through when they fail to authenticate
+#![cfg(not(feature = "local"))]
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{encode, get_current_timestamp, Algorithm, EncodingKey, Header};
+use raktar::auth::jwt::{JwksSource, JwtConfig};
+use raktar::config::RegistryConfig;
+use raktar::repository::{DynRepository, InMemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use serde_json::json;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::http::send_request;
+use common::memory_storage::MemoryStorage;
+
+const ISSUER: &str = "https://cognito-idp.eu-west-1.amazonaws.com/eu-west-1_test";
+const AUDIENCE: &str = "test-client";
+const PRIVATE_KEY: &[u8] = include_bytes!("fixtures/jwt/private_key.pem");
+const MY_TOKENS_QUERY: &str = "{ myTokens { name } }";
+
+#[tokio::test]
+#[traced_test]
+async fn test_signed_token_is_accepted() {
+    let app = build_app();
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": AUDIENCE,
+        "exp": get_current_timestamp() + 300,
+        "autogen_id": "1",
+    });
+
+    let (status, body) = send_request(&app, graphql_request(&sign(&claims))).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, r#"{"data":{"myTokens":[]}}"#);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_unsigned_token_is_rejected() {
+    let app = build_app();
+    let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"RS256","kid":"test-key-1"}"#);
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": AUDIENCE,
+        "exp": get_current_timestamp() + 300,
+        "autogen_id": "1",
+    });
+    let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+    let token = format!(
+        "{}.{}.{}",
+        header,
+        payload,
+        URL_SAFE_NO_PAD.encode("forged")
+    );
+
+    let (_, body) = send_request(&app, graphql_request(&token)).await;
+
+    assert!(body.contains("failed to get claims from token"));
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_expired_token_is_rejected() {
+    let app = build_app();
+    let claims = json!({
+        "iss": ISSUER,
+        "aud": AUDIENCE,
+        "exp": get_current_timestamp() - 300,
+        "autogen_id": "1",
+    });
+
+    let (_, body) = send_request(&app, graphql_request(&sign(&claims))).await;
+
+    assert!(body.contains("failed to get claims from token"));
+}
+
+fn build_app() -> Router {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let jwks = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/jwt/jwks.json");
+    let config = RegistryConfig {
+        jwt: Some(JwtConfig {
+            issuer: ISSUER.to_string(),
+            audience: AUDIENCE.to_string(),
+            jwks: JwksSource::File(jwks.into()),
+        }),
+        ..RegistryConfig::for_domain("raktar.io")
+    };
+
+    build_router(repository, storage, config)
+}
+
+fn sign(claims: &serde_json::Value) -> String {
+    let mut header = Header::new(Algorithm::RS256);
+    header.kid = Some("test-key-1".to_string());
+    let key = EncodingKey::from_rsa_pem(PRIVATE_KEY).unwrap();
+
+    encode(&header, claims, &key).unwrap()
+}
+
+fn graphql_request(token: &str) -> Request<Body> {
+    let body = json!({ "query": MY_TOKENS_QUERY });
+    Request::builder()
+        .method("POST")
+        .uri("/gql")
+        .header("Authorization", format!("Bearer {}", token))
+        .header("Content-Type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
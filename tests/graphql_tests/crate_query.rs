@@ -1,10 +1,13 @@
 use async_graphql::{value, Request, Variables};
 use axum::body::Bytes;
+use chrono::NaiveDate;
 use raktar::auth::AuthenticatedUser;
 use raktar::cargo_api::publish::publish_crate;
 use raktar::graphql::schema::{build_schema, RaktarSchema};
-use raktar::repository::DynRepository;
+use raktar::models::download::DownloadCount;
+use raktar::repository::{DynRepository, InMemoryRepository};
 use raktar::storage::DynCrateStorage;
+use semver::Version;
 use serde::Deserialize;
 use std::sync::Arc;
 
@@ -17,7 +20,7 @@ async fn test_crate_query_with_head_version_works() {
     let repository = Arc::new(build_repository().await) as DynRepository;
     let schema = build_schema(repository.clone());
     let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
-    let user = AuthenticatedUser { id: 1 };
+    let user = AuthenticatedUser::new(1);
 
     // publish version 0.1.1
     let data = Bytes::from_static(CRATE_BYTES_V1);
@@ -61,7 +64,7 @@ async fn test_crate_query_returns_null_when_crate_version_is_missing() {
     let repository = Arc::new(build_repository().await) as DynRepository;
     let schema = build_schema(repository.clone());
     let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
-    let user = AuthenticatedUser { id: 1 };
+    let user = AuthenticatedUser::new(1);
 
     // publish version 0.1.1
     let data = Bytes::from_static(CRATE_BYTES_V1);
@@ -84,6 +87,77 @@ async fn test_crate_query_returns_null_when_crate_version_is_missing() {
     assert!(crate_version.as_null().is_some());
 }
 
+#[tokio::test]
+async fn test_crate_query_returns_download_counts() {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let schema = build_schema(repository.clone());
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let user = AuthenticatedUser::new(1);
+    for data in [CRATE_BYTES_V1, CRATE_BYTES_V2] {
+        publish_crate(
+            user.clone(),
+            storage.clone(),
+            repository.clone(),
+            Bytes::from_static(data),
+        )
+        .await
+        .expect("publish to succeed");
+    }
+
+    let count = |version: &str, day: u32, downloads: u64| DownloadCount {
+        crate_name: "testcrate_1".to_string(),
+        version: Version::parse(version).unwrap(),
+        date: NaiveDate::from_ymd_opt(2023, 6, day).unwrap(),
+        downloads,
+    };
+    assert!(repository
+        .add_downloads(vec![
+            count("0.1.1", 1, 3),
+            count("0.1.1", 2, 1),
+            count("0.1.2", 2, 4),
+        ])
+        .await
+        .is_empty());
+
+    let query = r#"
+    query CrateVersion($name: String!, $version: String) {
+      crateVersion(name: $name, version: $version) {
+        downloads
+        dailyDownloads { date downloads }
+        crate {
+          downloads
+          dailyDownloads { date downloads }
+        }
+      }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "name": "testcrate_1", "version": "0.1.1" }));
+    let response = schema
+        .execute(build_request(query, 1).variables(variables))
+        .await;
+
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        response.data,
+        value!({
+            "crateVersion": {
+                "downloads": 4,
+                "dailyDownloads": [
+                    { "date": "2023-06-01", "downloads": 3 },
+                    { "date": "2023-06-02", "downloads": 1 },
+                ],
+                "crate": {
+                    "downloads": 8,
+                    "dailyDownloads": [
+                        { "date": "2023-06-01", "downloads": 3 },
+                        { "date": "2023-06-02", "downloads": 5 },
+                    ],
+                },
+            }
+        })
+    );
+}
+
 async fn get_crate_version(schema: &RaktarSchema, name: &str) -> CrateVersion {
     let request = build_crate_request(1, name, None);
     let response = schema.execute(request).await;
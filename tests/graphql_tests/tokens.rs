@@ -1,6 +1,9 @@
 use async_graphql::{value, Name, Request, Value, Variables};
+use p384::ecdsa::SigningKey;
+use raktar::auth::PublicKey;
 use raktar::graphql::schema::build_schema;
-use raktar::repository::DynRepository;
+use raktar::repository::{DynRepository, InMemoryRepository};
+use rand::rngs::OsRng;
 use std::collections::HashSet;
 use std::sync::Arc;
 
@@ -115,6 +118,145 @@ fn extract_data(data: &Value, path: &[&str]) -> Value {
     actual
 }
 
+#[tokio::test]
+async fn test_scoped_token_generation() {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let schema = build_schema(repository);
+
+    let mutation = r#"
+    mutation GenerateToken($endpointScopes: [String!], $crateScopes: [String!]) {
+        generateToken(name: "ci", endpointScopes: $endpointScopes, crateScopes: $crateScopes) {
+            token {
+                endpointScopes
+                crateScopes
+            }
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({
+        "endpointScopes": ["publish-update", "yank"],
+        "crateScopes": ["raktar-*"],
+    }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        extract_data(&response.data, &["generateToken", "token"]),
+        value!({
+            "endpointScopes": ["publish-update", "yank"],
+            "crateScopes": ["raktar-*"],
+        })
+    );
+
+    let variables = Variables::from_value(value!({ "endpointScopes": ["publish"] }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+}
+
+#[tokio::test]
+async fn test_token_expiry_generation() {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let schema = build_schema(repository);
+
+    let mutation = r#"
+    mutation GenerateToken($expiresAt: DateTime) {
+        generateToken(name: "ci", expiresAt: $expiresAt) {
+            id
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "expiresAt": "2100-01-01T00:00:00Z" }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+
+    let variables = Variables::from_value(value!({ "expiresAt": "2000-01-01T00:00:00Z" }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+
+    let query = r#"
+    query {
+      myTokens {
+        createdAt
+        lastUsedAt
+        expiresAt
+      }
+    }"#;
+    let response = schema.execute(build_request(query, 1)).await;
+    assert_eq!(response.errors.len(), 0);
+    let tokens = match extract_data(&response.data, &["myTokens"]) {
+        Value::List(tokens) => tokens,
+        _ => panic!("tokens is not a list"),
+    };
+    assert_eq!(tokens.len(), 1);
+    assert!(matches!(
+        extract_data(&tokens[0], &["createdAt"]),
+        Value::String(_)
+    ));
+    assert_eq!(extract_data(&tokens[0], &["lastUsedAt"]), Value::Null);
+    assert_eq!(
+        extract_data(&tokens[0], &["expiresAt"]),
+        Value::String("2100-01-01T00:00:00+00:00".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_public_key_registration() {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let schema = build_schema(repository.clone());
+    let public_key = PublicKey::from(&SigningKey::random(&mut OsRng)).to_string();
+
+    let mutation = r#"
+    mutation RegisterPublicKey($publicKey: String!) {
+        registerPublicKey(name: "laptop", publicKey: $publicKey, crateScopes: ["raktar-*"]) {
+            name
+            publicKey
+            crateScopes
+        }
+    }
+    "#;
+    let variables = Variables::from_value(value!({ "publicKey": public_key.clone() }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables.clone()))
+        .await;
+    assert_eq!(response.errors.len(), 0);
+    assert_eq!(
+        extract_data(&response.data, &["registerPublicKey"]),
+        value!({
+            "name": "laptop",
+            "publicKey": public_key.clone(),
+            "crateScopes": ["raktar-*"],
+        })
+    );
+
+    // the same key can't be registered twice
+    let response = schema
+        .execute(build_request(mutation, 2).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+
+    let variables = Variables::from_value(value!({ "publicKey": "k3.public.AAAA" }));
+    let response = schema
+        .execute(build_request(mutation, 1).variables(variables))
+        .await;
+    assert_eq!(response.errors.len(), 1);
+
+    let key_id = public_key.parse::<PublicKey>().unwrap().id();
+    let token = repository
+        .get_auth_token(key_id.as_bytes())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(token.user_id, 1);
+    assert_eq!(token.public_key, Some(public_key));
+}
+
 fn build_generate_token_request(user_id: u32, name: &str) -> Request {
     let mutation = r#"
     mutation GenerateToken($name: String!) {
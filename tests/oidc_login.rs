@@ -0,0 +1,226 @@
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, Response, StatusCode};
+use axum::Router;
+use raktar::auth::oidc::{
+    DynIdentityProvider, IdentityClaims, IdentityProvider, LoginRequest, OidcConfig,
+};
+use raktar::config::RegistryConfig;
+use raktar::repository::{DynRepository, InMemoryRepository};
+use raktar::router::build_router_with_identity_provider;
+use raktar::storage::DynCrateStorage;
+use serde_json::json;
+use std::sync::Arc;
+use tower::ServiceExt;
+use tracing_test::traced_test;
+use url::Url;
+
+use common::http::send_request;
+use common::memory_storage::MemoryStorage;
+
+const AUTHORIZE_URL: &str = "https://idp.raktar.io/authorize";
+const VALID_CODE: &str = "valid-code";
+
+/// A provider that hands out an ID token for the valid code, for the nonce it's given.
+struct FakeProvider {
+    /// Issues ID tokens for some other login instead.
+    wrong_nonce: bool,
+    email_verified: bool,
+}
+
+impl Default for FakeProvider {
+    fn default() -> Self {
+        Self {
+            wrong_nonce: false,
+            email_verified: true,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityProvider for FakeProvider {
+    async fn authorization_url(&self, login: &LoginRequest) -> anyhow::Result<String> {
+        let url = Url::parse_with_params(AUTHORIZE_URL, &[("state", &login.state)])?;
+
+        Ok(url.into())
+    }
+
+    async fn exchange_code(
+        &self,
+        code: &str,
+        login: &LoginRequest,
+    ) -> anyhow::Result<IdentityClaims> {
+        if code != VALID_CODE {
+            anyhow::bail!("invalid code");
+        }
+        let nonce = if self.wrong_nonce {
+            "other-nonce".to_string()
+        } else {
+            login.nonce.clone()
+        };
+
+        Ok(IdentityClaims {
+            sub: "248289761001".to_string(),
+            email: Some("jane@raktar.io".to_string()),
+            email_verified: Some(self.email_verified),
+            name: Some("Jane Doe".to_string()),
+            given_name: None,
+            family_name: None,
+            nonce: Some(nonce),
+        })
+    }
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_login_creates_user_and_session() {
+    let app = build_app(FakeProvider::default());
+
+    let (state, cookie) = start_login(&app).await;
+    let response = send(&app, callback_request(VALID_CODE, &state, Some(&cookie))).await;
+
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+    let location = get_header(&response, header::LOCATION);
+    let session = location
+        .strip_prefix("https://raktar.io/#session=")
+        .expect("to be sent to the frontend with a session");
+    assert!(get_header(&response, header::SET_COOKIE).starts_with("raktar_login=;"));
+
+    let query = json!({ "query": "{ user(id: \"1\") { login givenName familyName } }" });
+    let request = Request::builder()
+        .method("POST")
+        .uri("/gql")
+        .header("Authorization", format!("Bearer {}", session))
+        .header("Content-Type", "application/json")
+        .body(Body::from(query.to_string()))
+        .unwrap();
+    let (status, body) = send_request(&app, request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body,
+        r#"{"data":{"user":{"login":"jane@raktar.io","givenName":"Jane","familyName":"Doe"}}}"#
+    );
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_callback_needs_the_login_cookie() {
+    let app = build_app(FakeProvider::default());
+
+    let (state, cookie) = start_login(&app).await;
+
+    let response = send(&app, callback_request(VALID_CODE, &state, None)).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(&app, callback_request(VALID_CODE, "other", Some(&cookie))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = send(&app, callback_request("bad-code", &state, Some(&cookie))).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_id_token_for_another_login_is_rejected() {
+    let app = build_app(FakeProvider {
+        wrong_nonce: true,
+        ..FakeProvider::default()
+    });
+
+    let (state, cookie) = start_login(&app).await;
+    let response = send(&app, callback_request(VALID_CODE, &state, Some(&cookie))).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_unverified_email_is_rejected() {
+    let app = build_app(FakeProvider {
+        email_verified: false,
+        ..FakeProvider::default()
+    });
+
+    let (state, cookie) = start_login(&app).await;
+    let response = send(&app, callback_request(VALID_CODE, &state, Some(&cookie))).await;
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().get(header::LOCATION).is_none());
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_login_is_not_routed_without_oidc() {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let provider = Arc::new(FakeProvider::default()) as DynIdentityProvider;
+    let config = RegistryConfig::for_domain("api.raktar.io");
+    let app = build_router_with_identity_provider(repository, storage, config, Some(provider));
+
+    let request = Request::get("/auth/login").body(Body::empty()).unwrap();
+    let response = send(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+fn build_app(provider: FakeProvider) -> Router {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let provider = Arc::new(provider) as DynIdentityProvider;
+    let config = RegistryConfig {
+        oidc: Some(OidcConfig {
+            issuer: "https://idp.raktar.io".to_string(),
+            client_id: "raktar".to_string(),
+            client_secret: None,
+            redirect_url: "https://api.raktar.io/auth/callback".to_string(),
+            frontend_url: "https://raktar.io".to_string(),
+            session_secret: "a secret that is long enough to use".to_string(),
+        }),
+        ..RegistryConfig::for_domain("api.raktar.io")
+    };
+
+    build_router_with_identity_provider(repository, storage, config, Some(provider))
+}
+
+/// Starts logging in, and gives back the state sent to the provider and the login cookie.
+async fn start_login(app: &Router) -> (String, String) {
+    let request = Request::get("/auth/login").body(Body::empty()).unwrap();
+    let response = send(app, request).await;
+    assert_eq!(response.status(), StatusCode::SEE_OTHER);
+
+    let location = Url::parse(&get_header(&response, header::LOCATION)).unwrap();
+    assert!(location.as_str().starts_with(AUTHORIZE_URL));
+    let (_, state) = location
+        .query_pairs()
+        .find(|(key, _)| key == "state")
+        .unwrap();
+    let set_cookie = get_header(&response, header::SET_COOKIE);
+    let cookie = set_cookie.split(';').next().unwrap().to_string();
+    assert!(cookie.starts_with("raktar_login="));
+
+    (state.to_string(), cookie)
+}
+
+fn callback_request(code: &str, state: &str, cookie: Option<&str>) -> Request<Body> {
+    let mut builder = Request::get(format!("/auth/callback?code={}&state={}", code, state));
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::COOKIE, cookie);
+    }
+
+    builder.body(Body::empty()).unwrap()
+}
+
+async fn send(app: &Router, request: Request<Body>) -> Response<axum::body::BoxBody> {
+    app.clone().oneshot(request).await.unwrap()
+}
+
+fn get_header(response: &Response<axum::body::BoxBody>, name: header::HeaderName) -> String {
+    response
+        .headers()
+        .get(name)
+        .expect("the header to be set")
+        .to_str()
+        .unwrap()
+        .to_string()
+}
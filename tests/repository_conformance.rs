@@ -0,0 +1,289 @@
+mod common;
+
+use chrono::NaiveDate;
+use raktar::auth::AuthenticatedUser;
+use raktar::error::{AppError, AppResult};
+use raktar::models::crate_summary::CrateSummary;
+use raktar::models::download::DownloadCount;
+use raktar::models::index::PackageInfo;
+use raktar::models::metadata::Metadata;
+use raktar::models::user::{CognitoUserData, User};
+use raktar::repository::{DynRepository, InMemoryRepository, SqlRepository};
+use semver::Version;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Runs every test of the suite against the repository the expression builds.
+macro_rules! conformance_tests {
+    ($backend:ident, $build_repository:expr) => {
+        mod $backend {
+            use super::*;
+
+            async fn build_repository() -> DynRepository {
+                $build_repository
+            }
+
+            #[tokio::test]
+            async fn test_publish_and_read_back() {
+                super::test_publish_and_read_back(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_duplicate_version_is_rejected() {
+                super::test_duplicate_version_is_rejected(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_only_owners_can_publish() {
+                super::test_only_owners_can_publish(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_owners_without_a_user_are_listed() {
+                super::test_owners_without_a_user_are_listed(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_yanking_changes_the_index_revision() {
+                super::test_yanking_changes_the_index_revision(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_canonical_names() {
+                super::test_canonical_names(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_crate_pages() {
+                super::test_crate_pages(build_repository().await).await;
+            }
+
+            #[tokio::test]
+            async fn test_downloads_are_added_up_per_day() {
+                super::test_downloads_are_added_up_per_day(build_repository().await).await;
+            }
+        }
+    };
+}
+
+conformance_tests!(memory, Arc::new(InMemoryRepository::new()) as DynRepository);
+conformance_tests!(sql, Arc::new(build_sql_repository().await) as DynRepository);
+conformance_tests!(
+    dynamodb,
+    Arc::new(common::setup::build_repository().await) as DynRepository
+);
+
+async fn build_sql_repository() -> SqlRepository {
+    let path = std::env::temp_dir().join(format!("raktar-{}.db", Uuid::new_v4()));
+    let database_url = format!("sqlite://{}?mode=rwc", path.display());
+    SqlRepository::connect(&database_url).await.unwrap()
+}
+
+fn build_metadata(name: &str, vers: &str) -> Metadata {
+    let json = format!(
+        r#"{{"name":"{}","vers":"{}","deps":[],"features":{{"std":[]}},"authors":[],"description":"version {}","documentation":null,"homepage":null,"readme":null,"readme_file":null,"keywords":[],"categories":[],"license":null,"license_file":null,"repository":null,"badges":{{}},"links":null}}"#,
+        name, vers, vers
+    );
+    serde_json::from_str(&json).unwrap()
+}
+
+async fn publish(
+    repository: &DynRepository,
+    name: &str,
+    vers: &str,
+    user_id: u32,
+) -> AppResult<()> {
+    let metadata = build_metadata(name, vers);
+    let package_info = PackageInfo::from_metadata(metadata.clone(), "abc");
+    let version = Version::parse(vers).unwrap();
+    let user = AuthenticatedUser::new(user_id);
+    repository
+        .store_package_info(name, &version, package_info, metadata, &user)
+        .await
+}
+
+async fn test_publish_and_read_back(repository: DynRepository) {
+    publish(&repository, "foo", "0.10.0", 1).await.unwrap();
+    publish(&repository, "foo", "0.9.0", 1).await.unwrap();
+
+    // the max version only moves forward, and versions are compared as numbers
+    let summary = repository.get_crate_summary("foo").await.unwrap().unwrap();
+    assert_eq!(summary.max_version, Version::new(0, 10, 0));
+    assert_eq!(summary.description, "version 0.10.0");
+    assert_eq!(summary.owners, vec![1]);
+
+    let mut versions = repository.list_crate_versions("foo").await.unwrap();
+    versions.sort();
+    assert_eq!(
+        versions,
+        vec![Version::new(0, 9, 0), Version::new(0, 10, 0)]
+    );
+
+    let index_file = repository.get_package_info("foo").await.unwrap();
+    let mut versions: Vec<PackageInfo> = index_file
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    versions.sort_by(|a, b| a.vers.cmp(&b.vers));
+    assert_eq!(versions[0].vers, Version::new(0, 9, 0));
+    assert_eq!(versions[1].vers, Version::new(0, 10, 0));
+    assert_eq!(versions[1].features["std"], Vec::<String>::new());
+
+    let metadata = repository
+        .get_crate_metadata("foo", &Version::new(0, 9, 0))
+        .await
+        .unwrap();
+    assert_eq!(metadata, Some(build_metadata("foo", "0.9.0")));
+}
+
+async fn test_duplicate_version_is_rejected(repository: DynRepository) {
+    publish(&repository, "foo", "0.1.0", 1).await.unwrap();
+
+    let err = publish(&repository, "foo", "0.1.0", 1).await.unwrap_err();
+
+    assert!(matches!(err, AppError::DuplicateCrateVersion { .. }));
+}
+
+async fn test_only_owners_can_publish(repository: DynRepository) {
+    publish(&repository, "foo", "0.1.0", 1).await.unwrap();
+
+    let err = publish(&repository, "foo", "0.2.0", 2).await.unwrap_err();
+    assert!(matches!(err, AppError::Unauthorized(_)));
+
+    repository
+        .add_owners("foo", vec!["2".to_string(), "1".to_string()])
+        .await
+        .unwrap();
+    let mut owners = repository
+        .get_crate_summary("foo")
+        .await
+        .unwrap()
+        .unwrap()
+        .owners;
+    owners.sort();
+    assert_eq!(owners, vec![1, 2]);
+    publish(&repository, "foo", "0.2.0", 2).await.unwrap();
+    publish(&repository, "foo", "0.3.0", 1).await.unwrap();
+
+    let err = repository
+        .add_owners("bar", vec!["2".to_string()])
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AppError::NonExistentPackageInfo(_)));
+}
+
+async fn test_owners_without_a_user_are_listed(repository: DynRepository) {
+    let user_data = CognitoUserData {
+        login: "jane@raktar.io".to_string(),
+        given_name: "Jane".to_string(),
+        family_name: "Doe".to_string(),
+    };
+    let jane = repository.update_or_create_user(user_data).await.unwrap();
+    publish(&repository, "foo", "0.1.0", jane.id).await.unwrap();
+    let unknown_id = jane.id + 100;
+
+    repository
+        .add_owners("foo", vec![unknown_id.to_string()])
+        .await
+        .unwrap();
+
+    let owners = repository.list_owners("foo").await.unwrap();
+    assert_eq!(owners, vec![jane, User::unknown(unknown_id)]);
+    let err = repository.list_owners("bar").await.unwrap_err();
+    assert!(matches!(err, AppError::NonExistentPackageInfo(_)));
+}
+
+async fn test_yanking_changes_the_index_revision(repository: DynRepository) {
+    publish(&repository, "foo", "0.1.0", 1).await.unwrap();
+    let revision = repository.get_index_revision("foo").await.unwrap().unwrap();
+    assert_eq!(revision.revision, 1);
+
+    repository
+        .set_yanked("foo", &Version::new(0, 1, 0), true)
+        .await
+        .unwrap();
+
+    let revision = repository.get_index_revision("foo").await.unwrap().unwrap();
+    assert_eq!(revision.revision, 2);
+    let index_file = repository.get_package_info("foo").await.unwrap();
+    assert!(index_file.contains(r#""yanked":true"#));
+
+    let err = repository
+        .set_yanked("foo", &Version::new(0, 2, 0), true)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, AppError::NonExistentCrateVersion { .. }));
+}
+
+async fn test_canonical_names(repository: DynRepository) {
+    publish(&repository, "Foo-Bar", "0.1.0", 1).await.unwrap();
+
+    let name = repository.lookup_crate_name("foo_bar").await.unwrap();
+    assert_eq!(name.as_deref(), Some("Foo-Bar"));
+
+    // checking the name before publishing can race with another publish, so storing
+    // the crate checks it again
+    let err = publish(&repository, "foo_bar", "0.1.0", 1)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        AppError::CrateNameConflict { existing_name, .. } if existing_name == "Foo-Bar"
+    ));
+}
+
+async fn test_crate_pages(repository: DynRepository) {
+    for name in ["bar", "baz", "foo"] {
+        publish(&repository, name, "0.1.0", 1).await.unwrap();
+    }
+
+    let names =
+        |crates: Vec<CrateSummary>| -> Vec<String> { crates.into_iter().map(|c| c.name).collect() };
+    let first_page = repository.get_all_crate_details(None, 2, None).await;
+    assert_eq!(names(first_page.unwrap()), vec!["bar", "baz"]);
+    let second_page = repository
+        .get_all_crate_details(None, 2, Some("baz".to_string()))
+        .await;
+    assert_eq!(names(second_page.unwrap()), vec!["foo"]);
+    let filtered = repository
+        .get_all_crate_details(Some("ba".to_string()), 10, Some("bar".to_string()))
+        .await;
+    assert_eq!(names(filtered.unwrap()), vec!["baz"]);
+}
+
+fn count(crate_name: &str, version: &str, day: u32, downloads: u64) -> DownloadCount {
+    DownloadCount {
+        crate_name: crate_name.to_string(),
+        version: Version::parse(version).unwrap(),
+        date: NaiveDate::from_ymd_opt(2023, 6, day).unwrap(),
+        downloads,
+    }
+}
+
+async fn test_downloads_are_added_up_per_day(repository: DynRepository) {
+    assert!(repository
+        .add_downloads(vec![
+            count("foo", "0.1.0", 1, 2),
+            count("bar", "0.1.0", 1, 5),
+        ])
+        .await
+        .is_empty());
+    assert!(repository
+        .add_downloads(vec![
+            count("foo", "0.1.0", 1, 3),
+            count("foo", "0.1.0", 2, 1),
+        ])
+        .await
+        .is_empty());
+
+    let counts = repository.get_download_counts("foo").await.unwrap();
+    assert_eq!(
+        counts,
+        vec![count("foo", "0.1.0", 1, 5), count("foo", "0.1.0", 2, 1)]
+    );
+    assert!(repository
+        .get_download_counts("baz")
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use axum::http::StatusCode;
+use axum::Router;
+use chrono::{DateTime, Duration, Utc};
+use raktar::auth::{generate_new_token, TokenScopes};
+use raktar::config::RegistryConfig;
+use raktar::repository::{DynRepository, InMemoryRepository};
+use raktar::router::build_router;
+use raktar::storage::DynCrateStorage;
+use std::sync::Arc;
+use tracing_test::traced_test;
+
+use common::http::{build_get_request, send_request};
+use common::memory_storage::MemoryStorage;
+
+// authenticated requests for unknown crates are not found, rather than unauthorized
+const INDEX_URI: &str = "/un/kn/unknown_crate";
+
+#[tokio::test]
+#[traced_test]
+async fn test_expired_token_is_rejected() {
+    let (app, repository) = build_app();
+    let valid = store_token(&repository, Some(Utc::now() + Duration::days(1))).await;
+    let expired = store_token(&repository, Some(Utc::now() - Duration::seconds(1))).await;
+
+    let (status, _) = send_request(&app, build_get_request(INDEX_URI, Some(&valid))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = send_request(&app, build_get_request(INDEX_URI, Some(&expired))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+#[traced_test]
+async fn test_token_use_is_recorded() {
+    let (app, repository) = build_app();
+    let token = store_token(&repository, None).await;
+    let stored = repository
+        .get_auth_token(token.as_bytes())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(stored.created_at.is_some());
+    assert_eq!(stored.last_used_at, None);
+
+    let before = Utc::now();
+    let (status, _) = send_request(&app, build_get_request(INDEX_URI, Some(&token))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let first_use = repository
+        .get_auth_token(token.as_bytes())
+        .await
+        .unwrap()
+        .unwrap()
+        .last_used_at
+        .unwrap();
+    assert!(first_use >= before);
+
+    // using the token again right away isn't written
+    send_request(&app, build_get_request(INDEX_URI, Some(&token))).await;
+    let second_use = repository
+        .get_auth_token(token.as_bytes())
+        .await
+        .unwrap()
+        .unwrap()
+        .last_used_at;
+    assert_eq!(second_use, Some(first_use));
+}
+
+fn build_app() -> (Router, DynRepository) {
+    let repository = Arc::new(InMemoryRepository::new()) as DynRepository;
+    let storage = Arc::new(MemoryStorage::default()) as DynCrateStorage;
+    let app = build_router(
+        repository.clone(),
+        storage,
+        RegistryConfig::for_domain("raktar.io"),
+    );
+
+    (app, repository)
+}
+
+async fn store_token(repository: &DynRepository, expires_at: Option<DateTime<Utc>>) -> String {
+    let token = generate_new_token();
+    repository
+        .store_auth_token(
+            token.as_bytes(),
+            "test".to_string(),
+            1,
+            TokenScopes::default(),
+            expires_at,
+        )
+        .await
+        .unwrap();
+
+    token
+}